@@ -7,4 +7,8 @@ echo "check for arch x86_64-pc-windows-gnu" && cargo check --release --target=x8
 echo "check for arch wasm32-wasip1-threads" && cargo check --release --target=wasm32-wasip1-threads --no-default-features --features=tls
 # echo "check for arch wasm32-wasip2" && cargo check --release --target=wasm32-wasip2
 # echo "check for arch wasm32-wasi" && cargo check --release --target=wasm32-wasi
-echo "check for arch wasm32-unknown-unknown" && cargo check --release --target=wasm32-unknown-unknown --no-default-features --features=tls
\ No newline at end of file
+echo "check for arch wasm32-unknown-unknown" && cargo check --release --target=wasm32-unknown-unknown --no-default-features --features=tls
+echo "check for backend ring" && cargo check --release --no-default-features --features=ring,async-fs
+echo "check for backend aws-lc-rs" && cargo check --release --no-default-features --features=aws-lc-rs,async-fs
+echo "check for backend fips" && cargo check --release --no-default-features --features=fips,async-fs
+echo "check for backend native-tls" && cargo check --release --no-default-features --features=native-tls,async-fs
@@ -0,0 +1,416 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+use reqwest::redirect;
+use reqwest::Client;
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+use url::Url;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    circuit::CircuitBreaker, concurrency::ConcurrencyLimiter, ratelimit::RateLimiter,
+    replica::Replicas, CircuitBreakerConfig, ConcurrencyLimits, ProxyConfig, RateLimiterConfig,
+    ReplicaStrategy, RetryPolicy,
+};
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+use crate::{tls, HostClient, HostPattern, TlsConfig};
+use crate::{Error, HttpClient, Result, Timeouts};
+
+/// Same limit as the default reqwest redirect policy.
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+const MAX_REDIRECTS: usize = 10;
+
+/// Fallible builder for [`HttpClient`].
+///
+/// Every `HttpClient::new` variant goes through this builder, so all targets
+/// share the same construction path.
+#[derive(Clone, Debug)]
+pub struct HttpClientBuilder {
+    base_url: Url,
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    tls_config: Option<TlsConfig>,
+    default_headers: HeaderMap,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<ProxyConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy_env: bool,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+    timeouts: Timeouts,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry_policy: Option<RetryPolicy>,
+    #[cfg(not(target_arch = "wasm32"))]
+    generate_idempotency_keys: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limiter: Option<RateLimiterConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    concurrency_limits: Option<ConcurrencyLimits>,
+    #[cfg(not(target_arch = "wasm32"))]
+    replica_urls: Vec<Url>,
+    #[cfg(not(target_arch = "wasm32"))]
+    replica_strategy: ReplicaStrategy,
+    #[cfg(not(target_arch = "wasm32"))]
+    replica_cool_down: Duration,
+}
+
+/// How long a replica that failed is tried last.
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_REPLICA_COOL_DOWN: Duration = Duration::from_secs(30);
+
+impl HttpClientBuilder {
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+            tls_config: None,
+            default_headers: HeaderMap::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy_env: true,
+            #[cfg(unix)]
+            unix_socket: None,
+            timeouts: Timeouts::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            connect_timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            retry_policy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            generate_idempotency_keys: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            circuit_breaker: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limiter: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            concurrency_limits: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            replica_urls: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            replica_strategy: ReplicaStrategy::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            replica_cool_down: DEFAULT_REPLICA_COOL_DOWN,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Base urls of replicas of the same API, the first one is also the base url.
+    ///
+    /// Relative-endpoint calls like `get` and `post_json` pick a replica with
+    /// the [`ReplicaStrategy`] and move to the next one when the connection
+    /// fails or the answer is a 5xx. Calls with a full url are not affected.
+    /// A `POST` or `PATCH` answered with a 5xx only moves on when the retry
+    /// policy has `retry_non_idempotent`, send it with an `Idempotency-Key` then.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn base_urls(mut self, base_urls: Vec<Url>) -> Self {
+        if let Some(base_url) = base_urls.first() {
+            self.base_url = base_url.clone();
+        }
+        self.replica_urls = base_urls;
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn replica_strategy(mut self, strategy: ReplicaStrategy) -> Self {
+        self.replica_strategy = strategy;
+        self
+    }
+
+    /// How long a replica is tried only after the healthy ones once it failed, 30 seconds by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn replica_cool_down(mut self, cool_down: Duration) -> Self {
+        self.replica_cool_down = cool_down;
+        self
+    }
+
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    pub fn tls_config(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Replaces all default headers sent with every request.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers = headers;
+        self
+    }
+
+    /// Adds a single default header, keeping the ones already set.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.insert(name, value);
+        self
+    }
+
+    /// Sends every request through this proxy, the proxy environment variables are then ignored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Whether `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` are honored
+    /// when no proxy is set, they are by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy_env(mut self, honor: bool) -> Self {
+        self.proxy_env = honor;
+        self
+    }
+
+    /// Sends every request over this Unix socket, whatever the host of its url.
+    ///
+    /// Requests keep the scheme of their url, use `http://` urls for plain HTTP
+    /// over the socket. Proxy settings are ignored. A `unix:///path/to.sock`
+    /// base url sets the socket as well.
+    #[cfg(unix)]
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Default read and total timeouts of every request, see [`HttpClient::with_timeouts`]
+    /// to change them for some calls.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Timeout for opening a connection, TLS handshake included.
+    ///
+    /// Connections are pooled and shared by every call, so unlike the other
+    /// timeouts this one is set for the whole client.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Retries failed requests with this policy, see [`HttpClient::with_retry_policy`]
+    /// to change it for some calls. Without one every request is sent once.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Sends every `POST` and `PATCH` with a new `Idempotency-Key`, the same one
+    /// on each retry. Errors carry the key, see [`Error::idempotency_key`](crate::Error::idempotency_key).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn generate_idempotency_keys(mut self, generate: bool) -> Self {
+        self.generate_idempotency_keys = generate;
+        self
+    }
+
+    /// Fails requests fast while their host keeps failing, the state is shared by
+    /// the clones of the client. See [`HttpClient::circuit_state`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// Keeps requests under these rate limits, the buckets are shared by the
+    /// clones of the client. Every retry takes a token as well.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn rate_limiter(mut self, config: RateLimiterConfig) -> Self {
+        self.rate_limiter = Some(config);
+        self
+    }
+
+    /// Caps the requests in flight, callers over the cap queue for a slot and
+    /// the time they wait counts toward the total timeout.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn concurrency_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.concurrency_limits = Some(limits);
+        self
+    }
+
+    /// Moves the socket path of a `unix://` base url to `unix_socket`.
+    fn take_unix_base(&mut self) -> anyhow::Result<()> {
+        if self.base_url.scheme() != "unix" {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        {
+            let path = self
+                .base_url
+                .to_file_path()
+                .map_err(|_| anyhow::anyhow!("{} is not a unix socket path", self.base_url))?;
+            self.unix_socket = Some(path);
+            self.base_url = Url::parse("http://localhost/")?;
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!("unix sockets are not supported on this platform")
+    }
+
+    /// reqwest builder with the settings every internal client shares.
+    fn client_builder(&self) -> anyhow::Result<reqwest::ClientBuilder> {
+        let builder = Client::builder().default_headers(self.default_headers.clone());
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.proxy()?),
+            None if !self.proxy_env => builder.no_proxy(),
+            None => builder,
+        };
+        #[cfg(unix)]
+        let builder = match &self.unix_socket {
+            Some(path) => builder.unix_socket(path.clone()),
+            None => builder,
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = match self.connect_timeout {
+            Some(timeout) => builder.connect_timeout(timeout),
+            None => builder,
+        };
+        Ok(builder)
+    }
+
+    /// Fails with [`Error::Config`] when the settings are invalid, the TLS
+    /// material is unreadable included.
+    pub fn build(self) -> Result<HttpClient> {
+        self.build_client().map_err(|err| Error::Config(err.into()))
+    }
+
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    fn build_client(mut self) -> anyhow::Result<HttpClient> {
+        self.take_unix_base()?;
+        let tls_config = self.tls_config.take().unwrap_or_default();
+        let host_configs = tls_config.host_configs.clone().unwrap_or_default();
+        let hosts = host_configs
+            .iter()
+            .map(|(host, _)| host.clone())
+            .collect::<Vec<_>>();
+
+        let (client, tls_connector) =
+            tls_client(self.client_builder()?, &tls_config, &hosts, None)?;
+        let host_clients = host_configs
+            .iter()
+            .enumerate()
+            .map(|(index, (host, host_tls_config))| {
+                if host_tls_config.host_configs.is_some() {
+                    anyhow::bail!("TLS config for {} has host_configs of its own", host);
+                }
+                let (client, tls_connector) =
+                    tls_client(self.client_builder()?, host_tls_config, &hosts, Some(index))?;
+                Ok(HostClient {
+                    host: host.clone(),
+                    client,
+                    tls_connector,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.into_client(client, tls_connector, host_clients)
+    }
+
+    #[cfg(all(not(feature = "tls-core"), not(target_arch = "wasm32")))]
+    fn build_client(mut self) -> anyhow::Result<HttpClient> {
+        self.take_unix_base()?;
+        if self.base_url.scheme() == "https" {
+            anyhow::bail!("https is not supported in this build");
+        }
+        let client = self.client_builder()?.build()?;
+        self.into_client(client)
+    }
+
+    /// The client with the state shared by its clones, the same on every native build.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn into_client(
+        self,
+        client: Client,
+        #[cfg(feature = "tls-core")] tls_connector: tls::TlsConnector,
+        #[cfg(feature = "tls-core")] host_clients: Vec<HostClient>,
+    ) -> anyhow::Result<HttpClient> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.validate()?;
+        }
+        if let Some(concurrency_limits) = &self.concurrency_limits {
+            concurrency_limits.validate()?;
+        }
+        Ok(HttpClient {
+            base_url: self.base_url,
+            client,
+            #[cfg(feature = "tls-core")]
+            tls_connector,
+            #[cfg(feature = "tls-core")]
+            host_clients,
+            timeouts: self.timeouts,
+            connect_timeout: self.connect_timeout,
+            retry_policy: self.retry_policy,
+            generate_idempotency_keys: self.generate_idempotency_keys,
+            idempotency_key: None,
+            circuit_breaker: self
+                .circuit_breaker
+                .map(|config| Arc::new(CircuitBreaker::new(config))),
+            rate_limiter: self
+                .rate_limiter
+                .map(|config| Arc::new(RateLimiter::new(config))),
+            concurrency_limiter: self
+                .concurrency_limits
+                .map(|limits| Arc::new(ConcurrencyLimiter::new(limits))),
+            replicas: (self.replica_urls.len() > 1).then(|| {
+                Arc::new(Replicas::new(
+                    self.replica_urls,
+                    self.replica_strategy,
+                    self.replica_cool_down,
+                ))
+            }),
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn build_client(mut self) -> anyhow::Result<HttpClient> {
+        self.take_unix_base()?;
+        let client = self.client_builder()?.build()?;
+        Ok(HttpClient {
+            base_url: self.base_url,
+            client,
+            timeouts: self.timeouts,
+        })
+    }
+}
+
+/// Builds the reqwest client for one TLS config.
+///
+/// `host` is the index in `hosts` the config belongs to, `None` for the default one.
+/// Redirects that would need another config are refused, since the connection
+/// would be made with the wrong CA and client identity.
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+fn tls_client(
+    builder: reqwest::ClientBuilder,
+    tls_config: &TlsConfig,
+    hosts: &[HostPattern],
+    host: Option<usize>,
+) -> anyhow::Result<(Client, tls::TlsConnector)> {
+    let tls_connector = tls_config.connector()?;
+    let mut builder = tls::use_connector(builder, &tls_connector).tls_info(true);
+    if !hosts.is_empty() {
+        let hosts = hosts.to_vec();
+        builder = builder.redirect(redirect::Policy::custom(move |attempt| {
+            let target = attempt.url().host_str().and_then(|target| {
+                hosts
+                    .iter()
+                    .position(|pattern| pattern.matches_host(target))
+            });
+            if target != host {
+                let message = format!("redirect to {} needs another TLS config", attempt.url());
+                attempt.error(message)
+            } else if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }));
+    }
+    Ok((builder.build()?, tls_connector))
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Settings of the per host circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures, errors or 5xx responses, that open the circuit of a host.
+    pub failure_threshold: u32,
+    /// How long an open circuit fails calls fast before letting a probe through.
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of the circuit of a host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through.
+    Closed,
+    /// Requests fail fast with a [`CircuitOpenError`].
+    Open,
+    /// The cool-down is over, the next request is a probe that closes or reopens the circuit.
+    HalfOpen,
+}
+
+/// Request refused without being sent because the circuit of its host is open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitOpenError {
+    pub host: String,
+    /// Time left before a probe is let through.
+    pub retry_in: Duration,
+    /// Key of a `POST` or `PATCH` sent with an `Idempotency-Key`.
+    pub idempotency_key: Option<String>,
+}
+
+impl fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "circuit open for {}, next probe in {:?}",
+            self.host, self.retry_in
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+#[derive(Debug, Default)]
+struct HostCircuit {
+    failures: u32,
+    /// Set while open, when the circuit opened or the last probe started.
+    opened_at: Option<Instant>,
+}
+
+/// Circuits of every host, shared by the clones of a client.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    hosts: Mutex<HashMap<String, HostCircuit>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Lets the request through or fails fast, an open circuit past its cool-down
+    /// lets one probe through per cool-down.
+    pub(crate) fn acquire(&self, host: &str) -> Result<(), CircuitOpenError> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(opened_at) = hosts
+            .get_mut(host)
+            .and_then(|circuit| circuit.opened_at.as_mut())
+        else {
+            return Ok(());
+        };
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.config.cool_down {
+            return Err(CircuitOpenError {
+                host: host.to_string(),
+                retry_in: self.config.cool_down - elapsed,
+                idempotency_key: None,
+            });
+        }
+        *opened_at = Instant::now();
+        Ok(())
+    }
+
+    pub(crate) fn record(&self, host: &str, success: bool) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if success {
+            hosts.remove(host);
+            return;
+        }
+        let circuit = hosts.entry(host.to_string()).or_default();
+        circuit.failures += 1;
+        if circuit.opened_at.is_some() || circuit.failures >= self.config.failure_threshold {
+            circuit.opened_at = Some(Instant::now());
+        }
+    }
+
+    pub(crate) fn state(&self, host: &str) -> CircuitState {
+        let hosts = self.hosts.lock().unwrap();
+        self.circuit_state(hosts.get(host))
+    }
+
+    /// State of every host whose circuit is not closed.
+    pub(crate) fn states(&self) -> Vec<(String, CircuitState)> {
+        let hosts = self.hosts.lock().unwrap();
+        hosts
+            .iter()
+            .map(|(host, circuit)| (host.clone(), self.circuit_state(Some(circuit))))
+            .filter(|(_, state)| *state != CircuitState::Closed)
+            .collect()
+    }
+
+    fn circuit_state(&self, circuit: Option<&HostCircuit>) -> CircuitState {
+        match circuit.and_then(|circuit| circuit.opened_at) {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.config.cool_down => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+}
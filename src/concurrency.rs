@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps on the requests in flight, shared by the clones of a client.
+///
+/// A request holds its slots until its `Response` is dropped, so a download
+/// keeps them while its body is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    pub max_in_flight: Option<usize>,
+    pub max_in_flight_per_host: Option<usize>,
+}
+
+impl ConcurrencyLimits {
+    /// Refuses caps that would leave every request waiting.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        if self.max_in_flight == Some(0) || self.max_in_flight_per_host == Some(0) {
+            anyhow::bail!("concurrency limits {:?} never let a request through", self);
+        }
+        Ok(())
+    }
+}
+
+/// Slots of a request, stored in the extensions of its response.
+#[derive(Clone, Debug)]
+pub(crate) struct InFlight {
+    _permits: Arc<Vec<OwnedSemaphorePermit>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ConcurrencyLimiter {
+    global: Option<Arc<Semaphore>>,
+    per_host: Option<usize>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(limits: ConcurrencyLimits) -> Self {
+        Self {
+            global: limits
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max))),
+            per_host: limits.max_in_flight_per_host,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a slot of the host, then for a global one. Waiters are served
+    /// in the order they arrived, and one waiting on a busy host does not hold
+    /// a global slot meanwhile.
+    pub(crate) async fn acquire(&self, host: &str) -> InFlight {
+        let host_semaphore = self.per_host.map(|max| {
+            let mut hosts = self.hosts.lock().unwrap();
+            hosts
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone()
+        });
+        let mut permits = Vec::new();
+        for semaphore in [host_semaphore, self.global.clone()].into_iter().flatten() {
+            // the semaphores are never closed
+            permits.push(semaphore.acquire_owned().await.unwrap());
+        }
+        InFlight {
+            _permits: Arc::new(permits),
+        }
+    }
+}
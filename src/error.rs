@@ -0,0 +1,350 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::TimeoutError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{CircuitOpenError, RateLimitedError};
+
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Bytes of the body kept in a [`StatusError`].
+pub(crate) const BODY_SNIPPET_LEN: usize = 1024;
+
+/// Everything that can go wrong in an [`HttpClient`](crate::HttpClient) call.
+///
+/// The typed errors of this crate are kept as sources, so they can still be
+/// found walking [`std::error::Error::source`] once converted to `anyhow`.
+///
+/// The errors of a request carry its `Idempotency-Key`, if it had one, next to
+/// the failure, read it with [`Error::idempotency_key`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The endpoint could not be joined to the base url.
+    UrlJoin {
+        base_url: Url,
+        endpoint: String,
+        source: url::ParseError,
+    },
+    /// The client settings are invalid, reported when building the client.
+    Config(BoxError),
+    /// The connection could not be opened, DNS failures included.
+    Connect {
+        source: reqwest::Error,
+        idempotency_key: Option<String>,
+    },
+    /// The TLS handshake failed or the server certificate was rejected. The source
+    /// is a [`PinMismatchError`](crate::PinMismatchError) or a
+    /// [`CertificateRejectedError`](crate::CertificateRejectedError) when one of those was the cause.
+    Tls {
+        source: BoxError,
+        idempotency_key: Option<String>,
+    },
+    Timeout(Box<TimeoutError>),
+    /// The server answered with a status that is not a success.
+    Status(Box<StatusError>),
+    /// Any other failure of the request, like a connection reset or a refused redirect.
+    Request {
+        source: reqwest::Error,
+        idempotency_key: Option<String>,
+    },
+    /// The body is not the JSON that was expected.
+    Decode {
+        url: Url,
+        source: serde_json::Error,
+        idempotency_key: Option<String>,
+    },
+    Io(std::io::Error),
+    /// A zip or tar archive could not be written or extracted.
+    Archive(BoxError),
+    #[cfg(not(target_arch = "wasm32"))]
+    CircuitOpen(CircuitOpenError),
+    #[cfg(not(target_arch = "wasm32"))]
+    RateLimited(RateLimitedError),
+}
+
+impl Error {
+    /// Key of the `POST` or `PATCH` that failed, keep it to reconcile the call
+    /// with the server.
+    pub fn idempotency_key(&self) -> Option<&str> {
+        match self {
+            Error::Connect {
+                idempotency_key, ..
+            }
+            | Error::Tls {
+                idempotency_key, ..
+            }
+            | Error::Request {
+                idempotency_key, ..
+            }
+            | Error::Decode {
+                idempotency_key, ..
+            } => idempotency_key.as_deref(),
+            Error::Timeout(timeout_err) => timeout_err.idempotency_key.as_deref(),
+            Error::Status(status_err) => status_err.idempotency_key.as_deref(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::CircuitOpen(open_err) => open_err.idempotency_key.as_deref(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::RateLimited(limited_err) => limited_err.idempotency_key.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Sets the key of the request on the errors that carry one, unless already set.
+    pub(crate) fn with_idempotency_key(mut self, key: Option<String>) -> Self {
+        let slot = match &mut self {
+            Error::Connect {
+                idempotency_key, ..
+            }
+            | Error::Tls {
+                idempotency_key, ..
+            }
+            | Error::Request {
+                idempotency_key, ..
+            }
+            | Error::Decode {
+                idempotency_key, ..
+            } => idempotency_key,
+            Error::Timeout(timeout_err) => &mut timeout_err.idempotency_key,
+            Error::Status(status_err) => &mut status_err.idempotency_key,
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::CircuitOpen(open_err) => &mut open_err.idempotency_key,
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::RateLimited(limited_err) => &mut limited_err.idempotency_key,
+            _ => return self,
+        };
+        if slot.is_none() {
+            *slot = key;
+        }
+        self
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Status(status_err) => Some(status_err.status),
+            _ => None,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::Timeout(_))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn connect(source: reqwest::Error) -> Self {
+        Error::Connect {
+            source,
+            idempotency_key: None,
+        }
+    }
+
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    pub(crate) fn tls(source: impl Into<BoxError>) -> Self {
+        Error::Tls {
+            source: source.into(),
+            idempotency_key: None,
+        }
+    }
+
+    pub(crate) fn request(source: reqwest::Error) -> Self {
+        Error::Request {
+            source,
+            idempotency_key: None,
+        }
+    }
+
+    pub(crate) fn archive(err: impl Into<BoxError>) -> Self {
+        Error::Archive(err.into())
+    }
+}
+
+/// Only the context is printed, the cause is the source, as `{:#}` on an
+/// `anyhow::Error` shows the whole chain.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UrlJoin {
+                base_url, endpoint, ..
+            } => write!(f, "cannot join {} to {}", endpoint, base_url),
+            Error::Config(_) => f.write_str("invalid client settings"),
+            Error::Connect { .. } => f.write_str("connection failed"),
+            Error::Tls { .. } => f.write_str("TLS failure"),
+            Error::Timeout(_) => f.write_str("request timed out"),
+            Error::Status(_) => f.write_str("unsuccessful response"),
+            Error::Request { .. } => f.write_str("request failed"),
+            Error::Decode { url, .. } => write!(f, "cannot decode the body from {}", url),
+            Error::Io(_) => f.write_str("I/O error"),
+            Error::Archive(_) => f.write_str("archive error"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::CircuitOpen(_) => f.write_str("circuit breaker open"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::RateLimited(_) => f.write_str("rate limited"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::UrlJoin { source, .. } => Some(source),
+            Error::Config(source) | Error::Tls { source, .. } | Error::Archive(source) => {
+                Some(source.as_ref())
+            }
+            Error::Connect { source, .. } | Error::Request { source, .. } => Some(source),
+            Error::Timeout(source) => Some(source.as_ref()),
+            Error::Status(status_err) => Some(status_err.as_ref()),
+            Error::Decode { source, .. } => Some(source),
+            Error::Io(source) => Some(source),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::CircuitOpen(source) => Some(source),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::RateLimited(source) => Some(source),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<TimeoutError> for Error {
+    fn from(err: TimeoutError) -> Self {
+        Error::Timeout(Box::new(err))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<CircuitOpenError> for Error {
+    fn from(err: CircuitOpenError) -> Self {
+        Error::CircuitOpen(err)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<RateLimitedError> for Error {
+    fn from(err: RateLimitedError) -> Self {
+        Error::RateLimited(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::archive(err)
+    }
+}
+
+/// Response whose status is not a success.
+#[derive(Clone, Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// Start of the body, lossily decoded as UTF-8.
+    pub body: String,
+    /// Key of a `POST` or `PATCH` sent with an `Idempotency-Key`.
+    pub idempotency_key: Option<String>,
+}
+
+impl StatusError {
+    /// Keeps the first [`BODY_SNIPPET_LEN`] bytes of `body`.
+    pub(crate) fn new(status: StatusCode, url: Url, headers: HeaderMap, body: &[u8]) -> Self {
+        let body = &body[..body.len().min(BODY_SNIPPET_LEN)];
+        Self {
+            status,
+            url,
+            headers,
+            body: String::from_utf8_lossy(body).into_owned(),
+            idempotency_key: None,
+        }
+    }
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} answered {}", self.url, self.status)?;
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for StatusError {}
+
+/// Error of the `*_json_with_error` calls, carries the error payload the server sent.
+#[derive(Debug)]
+pub enum ApiError<E> {
+    /// Unsuccessful response whose body is an `E`.
+    Api {
+        status: StatusCode,
+        url: Url,
+        headers: HeaderMap,
+        payload: E,
+        /// Key of a `POST` or `PATCH` sent with an `Idempotency-Key`.
+        idempotency_key: Option<String>,
+    },
+    /// Any other failure, an unsuccessful response whose body is not an `E` included.
+    Client(Error),
+}
+
+impl<E> ApiError<E> {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Api { status, .. } => Some(*status),
+            ApiError::Client(err) => err.status(),
+        }
+    }
+
+    pub fn idempotency_key(&self) -> Option<&str> {
+        match self {
+            ApiError::Api {
+                idempotency_key, ..
+            } => idempotency_key.as_deref(),
+            ApiError::Client(err) => err.idempotency_key(),
+        }
+    }
+
+    pub fn payload(&self) -> Option<&E> {
+        match self {
+            ApiError::Api { payload, .. } => Some(payload),
+            ApiError::Client(_) => None,
+        }
+    }
+}
+
+impl<E> From<Error> for ApiError<E> {
+    fn from(err: Error) -> Self {
+        ApiError::Client(err)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for ApiError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Api {
+                status,
+                url,
+                payload,
+                ..
+            } => write!(f, "{} answered {}: {:?}", url, status, payload),
+            ApiError::Client(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl<E: fmt::Debug> StdError for ApiError<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ApiError::Api { .. } => None,
+            ApiError::Client(err) => err.source(),
+        }
+    }
+}
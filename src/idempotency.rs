@@ -0,0 +1,49 @@
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Request;
+
+pub(crate) const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Key a request was sent with, stored in the extensions of its response so
+/// the errors built from the response carry it as well.
+#[derive(Clone, Debug)]
+pub(crate) struct SentKey(pub(crate) String);
+
+/// Random UUID v4, unique per logical call.
+pub(crate) fn generate() -> String {
+    // version 4 and the RFC 4122 variant, the other 122 bits are random
+    let bits = (fastrand::u128(..) & !(0xf << 76) & !(0x3 << 62)) | (0x4 << 76) | (0x2 << 62);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Sets the key on a `POST` or `PATCH` without one and returns the key the request carries.
+///
+/// `key` is the caller one, a key is generated when `generate_key` is set.
+pub(crate) fn apply(
+    request: &mut Request,
+    key: Option<&str>,
+    generate_key: bool,
+) -> anyhow::Result<Option<String>> {
+    if request.method().is_idempotent() {
+        return Ok(None);
+    }
+    if let Some(existing) = request.headers().get(&IDEMPOTENCY_KEY) {
+        return Ok(Some(existing.to_str()?.to_string()));
+    }
+    let key = match key {
+        Some(key) => key.to_string(),
+        None if generate_key => generate(),
+        None => return Ok(None),
+    };
+    request
+        .headers_mut()
+        .insert(IDEMPOTENCY_KEY, HeaderValue::from_str(&key)?);
+    Ok(Some(key))
+}
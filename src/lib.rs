@@ -1,17 +1,81 @@
+mod builder;
+#[cfg(not(target_arch = "wasm32"))]
+mod circuit;
+#[cfg(not(target_arch = "wasm32"))]
+mod concurrency;
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+mod idempotency;
+#[cfg(not(target_arch = "wasm32"))]
+mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
+mod ratelimit;
+#[cfg(not(target_arch = "wasm32"))]
+mod replica;
+#[cfg(not(target_arch = "wasm32"))]
+mod retry;
 #[cfg(test)]
 mod tests;
+mod timeout;
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+mod tls;
+
+pub use builder::HttpClientBuilder;
+#[cfg(not(target_arch = "wasm32"))]
+pub use circuit::{CircuitBreakerConfig, CircuitOpenError, CircuitState};
+#[cfg(not(target_arch = "wasm32"))]
+pub use concurrency::ConcurrencyLimits;
+pub use error::{ApiError, BoxError, Error, Result, StatusError};
+#[cfg(not(target_arch = "wasm32"))]
+pub use proxy::ProxyConfig;
+#[cfg(not(target_arch = "wasm32"))]
+pub use ratelimit::{RateLimit, RateLimitMode, RateLimitedError, RateLimiterConfig};
+#[cfg(not(target_arch = "wasm32"))]
+pub use replica::{ReplicaHealth, ReplicaStrategy};
+#[cfg(not(target_arch = "wasm32"))]
+pub use retry::{RetryErrorKind, RetryPolicy};
+pub use timeout::{TimeoutError, TimeoutPhase, Timeouts};
+#[cfg(all(
+    feature = "tls-core",
+    not(any(feature = "ring", feature = "aws-lc-rs", feature = "native-tls")),
+    not(target_arch = "wasm32")
+))]
+compile_error!(
+    "the tls-core feature needs a backend, enable one of tls, ring, aws-lc-rs, fips or native-tls"
+);
+
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+pub use rustls::CertificateError;
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+pub use tls::{
+    CaSource, CertificateInfo, CertificatePin, CertificateRejectedError, CertificateVerdict,
+    CertificateVerifier, ClientIdentity, CrlSource, FileTlsSource, HostPattern, KeyLogDestination,
+    PinHash, PinMismatchError, ResponseTlsDetails, ResponseTlsExt, RevocationCheck,
+    ServerCertificate, SystemRoots, TlsConfig, TlsMaterial, TlsMaterialSource, TlsReport,
+    TlsVersion,
+};
 
 use reqwest::Response;
-use reqwest::{header::HeaderMap, multipart, Client};
+use reqwest::{header::HeaderMap, multipart, Client, Request, RequestBuilder};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
 use std::io::Read;
 use std::io::Write;
 use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 use std::{fs::File, path::Path};
 #[cfg(all(feature = "async-fs", not(target_arch = "wasm32")))]
 use tokio::io::AsyncWriteExt;
+use tokio_util::bytes::Bytes;
 use url::Url;
 
+#[cfg(not(target_arch = "wasm32"))]
+use error::BODY_SNIPPET_LEN;
+
 #[derive(Debug, Clone)]
 pub enum ArchiveType {
     Zip,
@@ -31,83 +95,606 @@ impl ArchiveType {
 pub struct OkJson {
     pub ok: bool,
 }
-#[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
-#[derive(Clone, Debug)]
-pub struct TlsConfig {
-    pub insecure: Option<bool>,
-    pub private_chain_bytes: Option<Vec<u8>>,
-}
 
 #[derive(Clone, Debug)]
 pub struct HttpClient {
     base_url: Url,
     client: Client,
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    tls_connector: tls::TlsConnector,
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    host_clients: Vec<HostClient>,
+    timeouts: Timeouts,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry_policy: Option<RetryPolicy>,
+    #[cfg(not(target_arch = "wasm32"))]
+    generate_idempotency_keys: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    idempotency_key: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    circuit_breaker: Option<Arc<circuit::CircuitBreaker>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limiter: Option<Arc<ratelimit::RateLimiter>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    concurrency_limiter: Option<Arc<concurrency::ConcurrencyLimiter>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    replicas: Option<Arc<replica::Replicas>>,
+}
+
+/// Client built from one entry of [`TlsConfig::host_configs`].
+#[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+#[derive(Clone, Debug)]
+struct HostClient {
+    host: HostPattern,
+    client: Client,
+    tls_connector: tls::TlsConnector,
 }
 
 impl HttpClient {
-    #[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
+    pub fn builder(base_url: Url) -> HttpClientBuilder {
+        HttpClientBuilder::new(base_url)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the client cannot be built, use [`HttpClient::builder`] to get an error instead.
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
     pub fn new(
         base_url: Url,
         tls_config: Option<TlsConfig>,
         default_headers: Option<HeaderMap>,
     ) -> Self {
-        let mut builder = Client::builder();
+        let mut builder = HttpClientBuilder::new(base_url);
+        if let Some(tls_config) = tls_config {
+            builder = builder.tls_config(tls_config);
+        }
+        if let Some(headers) = default_headers {
+            builder = builder.default_headers(headers);
+        }
+        builder.build().expect("failed to build HttpClient")
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the client cannot be built, use [`HttpClient::builder`] to get an error instead.
+    #[cfg(not(all(feature = "tls-core", not(target_arch = "wasm32"))))]
+    pub fn new(base_url: Url, default_headers: Option<HeaderMap>) -> Self {
+        let mut builder = HttpClientBuilder::new(base_url);
         if let Some(headers) = default_headers {
             builder = builder.default_headers(headers);
         }
-        if base_url.scheme() == "https" {
-            builder = builder.use_rustls_tls();
+        builder.build().expect("failed to build HttpClient")
+    }
+
+    /// Opens a TLS connection to `endpoint` and reports what was negotiated, without sending a request.
+    ///
+    /// The connect timeout applies to the connection, the read timeout to the
+    /// handshake and the total timeout to both.
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    pub async fn inspect_tls(&self, endpoint: &str) -> Result<TlsReport> {
+        let url = self.join(endpoint)?;
+        let tls_connector = match self.host_client(&url) {
+            Some(host_client) => &host_client.tls_connector,
+            None => &self.tls_connector,
+        };
+        let timeouts = tls::InspectTimeouts {
+            connect: self.connect_timeout,
+            read: self.timeouts.read,
+        };
+        let inspect = tls::inspect(tls_connector.clone(), &url, timeouts);
+        let report = match self.timeouts.total {
+            Some(total) => {
+                tokio::time::timeout(total, inspect)
+                    .await
+                    .map_err(|_| TimeoutError {
+                        phase: TimeoutPhase::Total,
+                        after: Some(total),
+                        url: Some(url.clone()),
+                        idempotency_key: None,
+                    })?
+            }
+            None => inspect.await,
+        };
+        report.map_err(tls::inspect_error)
+    }
+
+    fn join(&self, endpoint: &str) -> Result<Url> {
+        self.base_url
+            .join(endpoint)
+            .map_err(|source| Error::UrlJoin {
+                base_url: self.base_url.clone(),
+                endpoint: endpoint.to_string(),
+                source,
+            })
+    }
+
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    fn host_client(&self, url: &Url) -> Option<&HostClient> {
+        let host = url.host_str()?;
+        self.host_clients
+            .iter()
+            .find(|host_client| host_client.host.matches_host(host))
+    }
+
+    /// The client whose TLS settings apply to the host of `url`.
+    #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+    fn client_for(&self, url: &Url) -> &Client {
+        match self.host_client(url) {
+            Some(host_client) => &host_client.client,
+            None => &self.client,
+        }
+    }
+
+    #[cfg(not(all(feature = "tls-core", not(target_arch = "wasm32"))))]
+    fn client_for(&self, _url: &Url) -> &Client {
+        &self.client
+    }
+
+    /// Copy of this client whose requests use the timeouts set in `timeouts`
+    /// in place of the client ones. The connection pool is shared.
+    pub fn with_timeouts(&self, timeouts: Timeouts) -> HttpClient {
+        let mut client = self.clone();
+        client.timeouts = self.timeouts.merge(timeouts);
+        client
+    }
+
+    /// Copy of this client that retries with `retry_policy`, `None` sends every request once.
+    /// The connection pool is shared.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_retry_policy(&self, retry_policy: Option<RetryPolicy>) -> HttpClient {
+        let mut client = self.clone();
+        client.retry_policy = retry_policy;
+        client
+    }
+
+    /// Copy of this client that sends `POST` and `PATCH` requests with this
+    /// `Idempotency-Key`, meant for a single logical call and its retries.
+    /// A key set in the request headers wins.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_idempotency_key(&self, key: impl Into<String>) -> HttpClient {
+        let mut client = self.clone();
+        client.idempotency_key = Some(key.into());
+        client
+    }
+
+    /// State of the circuit breaker for `host`, `None` when the client has no circuit breaker.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn circuit_state(&self, host: &str) -> Option<CircuitState> {
+        self.circuit_breaker
+            .as_ref()
+            .map(|circuit_breaker| circuit_breaker.state(host))
+    }
+
+    /// Hosts whose circuit is open or half-open, for dashboards.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn circuit_states(&self) -> Vec<(String, CircuitState)> {
+        self.circuit_breaker
+            .as_ref()
+            .map(|circuit_breaker| circuit_breaker.states())
+            .unwrap_or_default()
+    }
 
-            if let Some(tls_config) = tls_config {
-                if let Some(true) = tls_config.insecure {
-                    builder = builder.danger_accept_invalid_certs(true);
-                } else if let Some(private_chain_bytes) = tls_config.private_chain_bytes {
-                    let reqwest_certificate =
-                        reqwest::Certificate::from_pem(&private_chain_bytes).unwrap();
+    /// Health of every replica, empty when the client has a single base url.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn replica_health(&self) -> Vec<ReplicaHealth> {
+        self.replicas
+            .as_ref()
+            .map(|replicas| replicas.health())
+            .unwrap_or_default()
+    }
 
-                    // print!("cert_chain: {:?}", cert_chain.as_slice());
+    /// Sends the request with the timeouts and retry policy of this client,
+    /// every method goes through here. `endpoint` is set for the calls relative
+    /// to the base url, they can move to another replica.
+    async fn send(
+        &self,
+        request_builder: RequestBuilder,
+        endpoint: Option<&str>,
+    ) -> Result<Response> {
+        let request_builder = match self.timeouts.total {
+            Some(total) => request_builder.timeout(total),
+            None => request_builder,
+        };
+        let (client, request) = request_builder.build_split();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let deadline = self
+                .timeouts
+                .total
+                .map(|total| tokio::time::Instant::now() + total);
+            let mut request = request.map_err(Error::request)?;
+            let idempotency_key = idempotency::apply(
+                &mut request,
+                self.idempotency_key.as_deref(),
+                self.generate_idempotency_keys,
+            )
+            .map_err(|err| Error::Config(err.into()))?;
+            let result = match &self.retry_policy {
+                Some(policy) if policy.applies_to(request.method()) => {
+                    self.send_with_retries(&client, request, endpoint, deadline, policy)
+                        .await
+                }
+                _ => {
+                    self.send_attempt(&client, request, endpoint, deadline)
+                        .await
+                }
+            };
+            match (result, idempotency_key) {
+                (Ok(mut resp), Some(key)) => {
+                    resp.extensions_mut().insert(idempotency::SentKey(key));
+                    Ok(resp)
+                }
+                (result, key) => result.map_err(|err| err.with_idempotency_key(key)),
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = endpoint;
+            self.send_once(&client, request.map_err(Error::request)?)
+                .await
+        }
+    }
 
-                    builder = builder.add_root_certificate(reqwest_certificate);
+    /// Retries share the total timeout of the request, no retry starts when
+    /// the backoff would end past its deadline.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_with_retries(
+        &self,
+        client: &Client,
+        mut request: Request,
+        endpoint: Option<&str>,
+        deadline: Option<tokio::time::Instant>,
+        policy: &RetryPolicy,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let next_request = if attempt < policy.max_attempts {
+                request.try_clone()
+            } else {
+                None
+            };
+            let result = self.send_attempt(client, request, endpoint, deadline).await;
+            let Some(mut next_request) = next_request else {
+                return result;
+            };
+            let delay = match &result {
+                Ok(resp) if policy.retries_status(resp.status()) => {
+                    policy.status_delay(attempt, resp.status(), resp.headers())
                 }
+                Err(err) if policy.retries_error(err) => Some(policy.backoff(attempt)),
+                _ => None,
+            };
+            let Some(delay) = delay else {
+                return result;
+            };
+            if !until_deadline(&mut next_request, deadline, delay) {
+                return result;
             }
+            drop(result);
+            tokio::time::sleep(delay).await;
+            request = next_request;
+            attempt += 1;
         }
+    }
 
-        let client = builder.build().unwrap();
-        Self { base_url, client }
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_attempt(
+        &self,
+        client: &Client,
+        request: Request,
+        endpoint: Option<&str>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<Response> {
+        match (&self.replicas, endpoint) {
+            (Some(replicas), Some(endpoint)) => {
+                self.send_to_replicas(replicas, request, endpoint, deadline)
+                    .await
+            }
+            _ => self.send_once(client, request).await,
+        }
     }
 
-    #[cfg(all(not(feature = "tls"), not(target_arch = "wasm32")))]
-    pub fn new(base_url: Url, default_headers: Option<HeaderMap>) -> Self {
-        let mut builder = Client::builder();
-        if let Some(headers) = default_headers {
-            builder = builder.default_headers(headers);
+    /// Tries the replicas in the order of the strategy, moving to the next one
+    /// when the connection fails or the answer is a 5xx. A `POST` or `PATCH`
+    /// answered with a 5xx may have been applied, it only moves on when the
+    /// retry policy has `retry_non_idempotent`. No replica is tried past the
+    /// deadline of the total timeout.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_to_replicas(
+        &self,
+        replicas: &replica::Replicas,
+        request: Request,
+        endpoint: &str,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<Response> {
+        let order = replicas.order();
+        let replay_server_errors = request.method().is_idempotent()
+            || self
+                .retry_policy
+                .as_ref()
+                .is_some_and(|policy| policy.retry_non_idempotent);
+        let mut request = Some(request);
+        let mut result = Err(Error::Config("no replica to send the request to".into()));
+        for (position, index) in order.iter().copied().enumerate() {
+            let Some(mut attempt) = request.take() else {
+                break;
+            };
+            if position > 0 && !until_deadline(&mut attempt, deadline, Duration::ZERO) {
+                break;
+            }
+            if position + 1 < order.len() {
+                request = attempt.try_clone();
+            }
+            let base_url = replicas.base_url(index);
+            let url = base_url.join(endpoint).map_err(|source| Error::UrlJoin {
+                base_url: base_url.clone(),
+                endpoint: endpoint.to_string(),
+                source,
+            })?;
+            *attempt.url_mut() = url.clone();
+            let guard = Arc::new(replicas.start(index));
+            result = self.send_once(self.client_for(&url), attempt).await;
+            let (failed, next) = match &mut result {
+                Ok(resp) => {
+                    resp.extensions_mut().insert(guard);
+                    let failed = resp.status().is_server_error();
+                    (failed, failed && replay_server_errors)
+                }
+                Err(err) => {
+                    let failed = replica::is_connection_error(err);
+                    (failed, failed)
+                }
+            };
+            replicas.record(index, !failed);
+            if !next {
+                break;
+            }
+        }
+        result
+    }
+
+    /// One attempt, gated by the circuit breaker, the concurrency limits and the rate limiter.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_once(&self, client: &Client, mut request: Request) -> Result<Response> {
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.acquire(&host)?;
+        }
+        let in_flight = match &self.concurrency_limiter {
+            Some(concurrency_limiter) => {
+                let acquire = concurrency_limiter.acquire(&host);
+                Some(self.queue_within_total(&mut request, acquire).await?)
+            }
+            None => None,
+        };
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(&host).await?;
+        }
+        let mut result = self.execute(client, request).await;
+        if let (Some(in_flight), Ok(resp)) = (in_flight, &mut result) {
+            resp.extensions_mut().insert(in_flight);
         }
-        if base_url.scheme() == "https" {
-            panic!("https is not supported in this build");
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            let success = match &result {
+                Ok(resp) => !resp.status().is_server_error(),
+                Err(_) => false,
+            };
+            circuit_breaker.record(&host, success);
         }
+        if let (Some(rate_limiter), Ok(resp)) = (&self.rate_limiter, &result) {
+            rate_limiter.record(&host, resp.headers());
+        }
+        result
+    }
 
-        let client = builder.build().unwrap();
-        Self { base_url, client }
+    /// Awaits `queued` within the total timeout of `request`, which then gets
+    /// only what is left of it.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn queue_within_total<F: Future>(
+        &self,
+        request: &mut Request,
+        queued: F,
+    ) -> Result<F::Output> {
+        let Some(total) = request.timeout().copied() else {
+            return Ok(queued.await);
+        };
+        let started = tokio::time::Instant::now();
+        let timed_out = TimeoutError {
+            phase: TimeoutPhase::Total,
+            after: self.timeouts.total,
+            url: Some(request.url().clone()),
+            idempotency_key: None,
+        };
+        let output = tokio::time::timeout(total, queued)
+            .await
+            .map_err(|_| timed_out.clone())?;
+        let left = total.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            return Err(timed_out.into());
+        }
+        *request.timeout_mut() = Some(left);
+        Ok(output)
     }
 
     #[cfg(target_arch = "wasm32")]
-    pub fn new(base_url: Url, default_headers: Option<HeaderMap>) -> Self {
-        let mut builder = Client::builder();
-        if let Some(headers) = default_headers {
-            builder = builder.default_headers(headers);
+    async fn send_once(&self, client: &Client, request: Request) -> Result<Response> {
+        self.execute(client, request).await
+    }
+
+    async fn execute(&self, client: &Client, request: Request) -> Result<Response> {
+        let url = request.url().clone();
+        self.read_within(&url, client.execute(request))
+            .await?
+            .map_err(|err| self.send_error(err))
+    }
+
+    /// Reads the whole response body, the read timeout applies to every chunk.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn body(&self, mut resp: Response) -> Result<Bytes> {
+        let url = resp.url().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = self
+            .read_within(&url, resp.chunk())
+            .await?
+            .map_err(|err| self.send_error(err))?
+        {
+            body.extend_from_slice(&chunk);
         }
-        let client = builder.build().unwrap();
-        Self { base_url, client }
+        Ok(body.into())
     }
 
-    pub async fn get(
+    #[cfg(target_arch = "wasm32")]
+    async fn body(&self, resp: Response) -> Result<Bytes> {
+        resp.bytes().await.map_err(|err| self.send_error(err))
+    }
+
+    /// Decodes the body of a successful response, any other is a [`Error::Status`].
+    async fn json<T: DeserializeOwned>(&self, resp: Response) -> Result<T> {
+        if !resp.status().is_success() {
+            return Err(self.status_error(resp).await);
+        }
+        let key = sent_idempotency_key(&resp);
+        let url = resp.url().clone();
+        let body = self
+            .body(resp)
+            .await
+            .map_err(|err| err.with_idempotency_key(key.clone()))?;
+        serde_json::from_slice(&body).map_err(|source| Error::Decode {
+            url,
+            source,
+            idempotency_key: key,
+        })
+    }
+
+    /// Like [`HttpClient::json`], decoding the body of an unsuccessful response as an `E`.
+    async fn json_with_error<T: DeserializeOwned, E: DeserializeOwned>(
         &self,
-        endpoint: &str,
-        extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Response> {
-        let url = self.base_url.join(endpoint)?;
+        resp: Response,
+    ) -> std::result::Result<T, ApiError<E>> {
+        if resp.status().is_success() {
+            return Ok(self.json(resp).await?);
+        }
+        let key = sent_idempotency_key(&resp);
+        let status = resp.status();
+        let url = resp.url().clone();
+        let headers = resp.headers().clone();
+        let body = self
+            .body(resp)
+            .await
+            .map_err(|err| err.with_idempotency_key(key.clone()))?;
+        match serde_json::from_slice(&body) {
+            Ok(payload) => Err(ApiError::Api {
+                status,
+                url,
+                headers,
+                payload,
+                idempotency_key: key,
+            }),
+            Err(_) => {
+                let status_err = StatusError::new(status, url, headers, &body);
+                Err(Error::Status(Box::new(status_err))
+                    .with_idempotency_key(key)
+                    .into())
+            }
+        }
+    }
 
-        let mut request_builder = self.client.get(url);
+    /// Error for an unsuccessful response, with the start of its body.
+    async fn status_error(&self, resp: Response) -> Error {
+        let key = sent_idempotency_key(&resp);
+        let status = resp.status();
+        let url = resp.url().clone();
+        let headers = resp.headers().clone();
+        let body = self.body_snippet(resp).await;
+        Error::Status(Box::new(StatusError::new(status, url, headers, &body)))
+            .with_idempotency_key(key)
+    }
+
+    /// Reads the body until it is longer than a snippet, a body that cannot be read is cut short.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn body_snippet(&self, mut resp: Response) -> Vec<u8> {
+        let url = resp.url().clone();
+        let mut body = Vec::new();
+        while body.len() < BODY_SNIPPET_LEN {
+            match self.read_within(&url, resp.chunk()).await {
+                Ok(Ok(Some(chunk))) => body.extend_from_slice(&chunk),
+                _ => break,
+            }
+        }
+        body
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn body_snippet(&self, resp: Response) -> Vec<u8> {
+        resp.bytes().await.unwrap_or_default().to_vec()
+    }
+
+    /// Awaits `future`, failing with a read timeout when it takes longer than the read timeout.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn read_within<F: Future>(&self, url: &Url, future: F) -> Result<F::Output> {
+        match self.timeouts.read {
+            Some(read) => tokio::time::timeout(read, future).await.map_err(|_| {
+                TimeoutError {
+                    phase: TimeoutPhase::Read,
+                    after: Some(read),
+                    url: Some(url.clone()),
+                    idempotency_key: None,
+                }
+                .into()
+            }),
+            None => Ok(future.await),
+        }
+    }
+
+    /// There is no timer to race against on wasm, only the total timeout applies.
+    #[cfg(target_arch = "wasm32")]
+    async fn read_within<F: Future>(&self, _url: &Url, future: F) -> Result<F::Output> {
+        Ok(future.await)
+    }
+
+    fn send_error(&self, err: reqwest::Error) -> Error {
+        #[cfg(all(feature = "tls-core", not(target_arch = "wasm32")))]
+        {
+            let source = &err as &(dyn std::error::Error + 'static);
+            if let Some(handshake_err) = tls::handshake_error(source) {
+                return Error::tls(handshake_err);
+            }
+            if tls::is_tls_failure(source) {
+                return Error::tls(err);
+            }
+        }
+        if err.is_timeout() {
+            #[cfg(not(target_arch = "wasm32"))]
+            if err.is_connect() {
+                return TimeoutError {
+                    phase: TimeoutPhase::Connect,
+                    after: self.connect_timeout,
+                    url: err.url().cloned(),
+                    idempotency_key: None,
+                }
+                .into();
+            }
+            return TimeoutError {
+                phase: TimeoutPhase::Total,
+                after: self.timeouts.total,
+                url: err.url().cloned(),
+                idempotency_key: None,
+            }
+            .into();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if err.is_connect() {
+            return Error::connect(err);
+        }
+        Error::request(err)
+    }
+
+    pub async fn get(&self, endpoint: &str, extra_headers: Option<HeaderMap>) -> Result<Response> {
+        let url = self.join(endpoint)?;
+
+        let mut request_builder = self.client_for(&url).get(url);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -115,7 +702,7 @@ impl HttpClient {
             }
         }
 
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, Some(endpoint)).await?;
 
         Ok(resp)
     }
@@ -124,21 +711,32 @@ impl HttpClient {
         &self,
         endpoint: &str,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<T> {
+    ) -> Result<T> {
+        let resp = self.get(endpoint, extra_headers).await?;
+
+        self.json(resp).await
+    }
+
+    /// Like [`HttpClient::get_json`], with the body of an unsuccessful response
+    /// decoded as an `E`, the error payload of the API.
+    pub async fn get_json_with_error<T: DeserializeOwned, E: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        extra_headers: Option<HeaderMap>,
+    ) -> std::result::Result<T, ApiError<E>> {
         let resp = self.get(endpoint, extra_headers).await?;
 
-        let response = resp.json::<T>().await?;
-        Ok(response)
+        self.json_with_error(resp).await
     }
     pub async fn post<U: Serialize>(
         &self,
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Response> {
-        let url = self.base_url.join(endpoint)?;
+    ) -> Result<Response> {
+        let url = self.join(endpoint)?;
 
-        let mut request_builder = self.client.post(url).json(body);
+        let mut request_builder = self.client_for(&url).post(url).json(body);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -146,7 +744,7 @@ impl HttpClient {
             }
         }
 
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, Some(endpoint)).await?;
 
         Ok(resp)
     }
@@ -155,11 +753,21 @@ impl HttpClient {
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<T> {
+    ) -> Result<T> {
         let resp = self.post(endpoint, body, extra_headers).await?;
 
-        let response = resp.json::<T>().await?;
-        Ok(response)
+        self.json(resp).await
+    }
+
+    pub async fn post_json_with_error<T: DeserializeOwned, E: DeserializeOwned, U: Serialize>(
+        &self,
+        endpoint: &str,
+        body: &U,
+        extra_headers: Option<HeaderMap>,
+    ) -> std::result::Result<T, ApiError<E>> {
+        let resp = self.post(endpoint, body, extra_headers).await?;
+
+        self.json_with_error(resp).await
     }
 
     pub async fn patch<U: Serialize>(
@@ -167,10 +775,10 @@ impl HttpClient {
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Response> {
-        let url = self.base_url.join(endpoint)?;
+    ) -> Result<Response> {
+        let url = self.join(endpoint)?;
 
-        let mut request_builder = self.client.patch(url).json(body);
+        let mut request_builder = self.client_for(&url).patch(url).json(body);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -178,7 +786,7 @@ impl HttpClient {
             }
         }
 
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, Some(endpoint)).await?;
 
         Ok(resp)
     }
@@ -188,11 +796,21 @@ impl HttpClient {
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<T> {
+    ) -> Result<T> {
+        let resp = self.patch(endpoint, body, extra_headers).await?;
+
+        self.json(resp).await
+    }
+
+    pub async fn patch_json_with_error<T: DeserializeOwned, E: DeserializeOwned, U: Serialize>(
+        &self,
+        endpoint: &str,
+        body: &U,
+        extra_headers: Option<HeaderMap>,
+    ) -> std::result::Result<T, ApiError<E>> {
         let resp = self.patch(endpoint, body, extra_headers).await?;
 
-        let response = resp.json::<T>().await?;
-        Ok(response)
+        self.json_with_error(resp).await
     }
 
     pub async fn put<U: Serialize>(
@@ -200,10 +818,10 @@ impl HttpClient {
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Response> {
-        let url = self.base_url.join(endpoint)?;
+    ) -> Result<Response> {
+        let url = self.join(endpoint)?;
 
-        let mut request_builder = self.client.put(url).json(body);
+        let mut request_builder = self.client_for(&url).put(url).json(body);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -211,7 +829,7 @@ impl HttpClient {
             }
         }
 
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, Some(endpoint)).await?;
 
         Ok(resp)
     }
@@ -221,21 +839,31 @@ impl HttpClient {
         endpoint: &str,
         body: &U,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<T> {
+    ) -> Result<T> {
         let resp = self.put(endpoint, body, extra_headers).await?;
 
-        let response = resp.json::<T>().await?;
-        Ok(response)
+        self.json(resp).await
+    }
+
+    pub async fn put_json_with_error<T: DeserializeOwned, E: DeserializeOwned, U: Serialize>(
+        &self,
+        endpoint: &str,
+        body: &U,
+        extra_headers: Option<HeaderMap>,
+    ) -> std::result::Result<T, ApiError<E>> {
+        let resp = self.put(endpoint, body, extra_headers).await?;
+
+        self.json_with_error(resp).await
     }
 
     pub async fn delete(
         &self,
         endpoint: &str,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Response> {
-        let url = self.base_url.join(endpoint)?;
+    ) -> Result<Response> {
+        let url = self.join(endpoint)?;
 
-        let mut request_builder = self.client.delete(url);
+        let mut request_builder = self.client_for(&url).delete(url);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -243,7 +871,7 @@ impl HttpClient {
             }
         }
 
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, Some(endpoint)).await?;
 
         Ok(resp)
     }
@@ -252,11 +880,20 @@ impl HttpClient {
         &self,
         endpoint: &str,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<T> {
+    ) -> Result<T> {
+        let resp = self.delete(endpoint, extra_headers).await?;
+
+        self.json(resp).await
+    }
+
+    pub async fn delete_json_with_error<T: DeserializeOwned, E: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        extra_headers: Option<HeaderMap>,
+    ) -> std::result::Result<T, ApiError<E>> {
         let resp = self.delete(endpoint, extra_headers).await?;
 
-        let response = resp.json::<T>().await?;
-        Ok(response)
+        self.json_with_error(resp).await
     }
 
     #[cfg(all(feature = "async-fs", not(target_arch = "wasm32")))]
@@ -266,14 +903,14 @@ impl HttpClient {
         path: &Path,
         multipart_file_name: Option<String>,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let tmp_dir = tempfile::tempdir()?;
         let zip_file_name = String::from("test.zip");
         let tmp_file_buff = tmp_dir.path().join(zip_file_name.clone());
         let tmp_file_path = Path::new(&tmp_file_buff).to_owned();
         let cloned_path = tmp_file_path.clone();
         let to_be_zipped = path.to_owned();
-        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        tokio::task::spawn_blocking(move || -> Result<()> {
             let mut zip = zip::ZipWriter::new(std::fs::File::create(tmp_file_path)?);
             let options = zip::write::FileOptions::default()
                 .compression_method(zip::CompressionMethod::Stored)
@@ -283,7 +920,8 @@ impl HttpClient {
             zip.finish()?;
             Ok(())
         })
-        .await??;
+        .await
+        .map_err(Error::archive)??;
         self.post_file_path(
             url,
             cloned_path.as_path(),
@@ -300,21 +938,25 @@ impl HttpClient {
         path: &Path,
         multipart_file_name: Option<String>,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let tmp_dir = tempfile::tempdir()?;
         let tmp_file_buff = tmp_dir.path().join("test.zip");
         let tmp_file_path = Path::new(&tmp_file_buff).to_owned();
         let cloned_path = tmp_file_path.clone();
         let to_be_zipped = path.to_owned();
-        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        tokio::task::spawn_blocking(move || -> Result<()> {
             let mut zip = zip::ZipWriter::new(std::fs::File::create(tmp_file_path)?);
             let options = zip::write::FileOptions::default()
                 .compression_method(zip::CompressionMethod::Stored)
                 .unix_permissions(0o755);
             for file in walkdir::WalkDir::new(to_be_zipped) {
-                let file = file?;
+                let file = file.map_err(Error::archive)?;
                 let path = file.path();
-                let name = path.strip_prefix(path)?.to_str().unwrap();
+                let name = path
+                    .strip_prefix(path)
+                    .map_err(Error::archive)?
+                    .to_str()
+                    .unwrap();
                 if path.is_file() {
                     zip.start_file(name, options)?;
                     zip.write_all(&std::fs::read(path)?)?;
@@ -325,7 +967,8 @@ impl HttpClient {
             zip.finish()?;
             Ok(())
         })
-        .await??;
+        .await
+        .map_err(Error::archive)??;
         self.post_file_path(
             url,
             cloned_path.as_path(),
@@ -342,7 +985,7 @@ impl HttpClient {
         path: &Path,
         multipart_file_name: Option<String>,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
 
         let mut file = File::open(path)?;
@@ -369,7 +1012,7 @@ impl HttpClient {
         bytes: &'static [u8],
         mut multipart_file_name: Option<String>,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         if multipart_file_name.is_none() {
             multipart_file_name = Some(String::from("file"));
         }
@@ -378,7 +1021,8 @@ impl HttpClient {
 
         let part = multipart::Part::bytes(bytes)
             .file_name(name.clone())
-            .mime_str("application/octet-stream")?;
+            .mime_str("application/octet-stream")
+            .map_err(Error::request)?;
 
         let form = multipart::Form::new().part(multipart_file_name, part);
 
@@ -390,8 +1034,8 @@ impl HttpClient {
         url: Url,
         multipart_form: multipart::Form,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<()> {
-        let mut request_builder = self.client.post(url);
+    ) -> Result<()> {
+        let mut request_builder = self.client_for(&url).post(url);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
@@ -399,12 +1043,14 @@ impl HttpClient {
             }
         }
 
-        let response = request_builder.multipart(multipart_form).send().await?;
+        let response = self
+            .send(request_builder.multipart(multipart_form), None)
+            .await?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            Err(anyhow::anyhow!("Error uploading file"))
+            Err(self.status_error(response).await)
         }
 
         // .json::<T>()
@@ -415,26 +1061,20 @@ impl HttpClient {
         &self,
         url: Url,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Option<tokio_util::bytes::Bytes>> {
-        let mut request_builder = self.client.get(url);
+    ) -> Result<Option<tokio_util::bytes::Bytes>> {
+        let mut request_builder = self.client_for(&url).get(url);
 
         if let Some(headers) = extra_headers {
             for (name, value) in headers.iter() {
                 request_builder = request_builder.header(name, value);
             }
         }
-        let resp = request_builder.send().await?;
+        let resp = self.send(request_builder, None).await?;
         if resp.status().is_success() {
-            let mut answer: Option<tokio_util::bytes::Bytes> = None;
-            let bytes_answer = resp.bytes().await;
-            if let Ok(bytes_answer) = bytes_answer {
-                if !bytes_answer.is_empty() {
-                    answer = Some(bytes_answer);
-                }
-            }
-            Ok(answer)
+            let bytes_answer = self.body(resp).await?;
+            Ok((!bytes_answer.is_empty()).then_some(bytes_answer))
         } else {
-            Err(anyhow::anyhow!("Error downloading file"))
+            Err(self.status_error(resp).await)
         }
     }
 
@@ -444,11 +1084,11 @@ impl HttpClient {
         url: Url,
         path: &Path,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Option<PathBuf>> {
+    ) -> Result<Option<PathBuf>> {
         let file_buffer = self.get_file_buffer(url, extra_headers).await?;
-        if file_buffer.is_some() {
+        if let Some(file_buffer) = file_buffer {
             let mut file = tokio::fs::File::create(&path).await?;
-            file.write_all(&file_buffer.unwrap()).await?;
+            file.write_all(&file_buffer).await?;
 
             Ok(Some(path.to_path_buf()))
         } else {
@@ -461,11 +1101,11 @@ impl HttpClient {
         url: Url,
         path: &Path,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Option<PathBuf>> {
+    ) -> Result<Option<PathBuf>> {
         let file_buffer = self.get_file_buffer(url, extra_headers).await?;
-        if file_buffer.is_some() {
+        if let Some(file_buffer) = file_buffer {
             let mut file = File::create(path)?;
-            file.write_all(file_buffer.unwrap().as_ref())?;
+            file.write_all(file_buffer.as_ref())?;
 
             Ok(Some(path.to_path_buf()))
         } else {
@@ -479,40 +1119,43 @@ impl HttpClient {
         archive_type: &ArchiveType,
         dir: &Path,
         extra_headers: Option<HeaderMap>,
-    ) -> anyhow::Result<Option<PathBuf>> {
+    ) -> Result<Option<PathBuf>> {
         let file_buffer = self.get_file_buffer(url, extra_headers).await?;
         if let Some(file_buffer) = file_buffer {
             match archive_type {
                 ArchiveType::Zip => {
                     let dir = dir.to_owned();
-                    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+                    tokio::task::spawn_blocking(move || -> Result<()> {
                         let mut archive = zip::ZipArchive::new(std::io::Cursor::new(file_buffer))?;
                         archive.extract(dir)?;
 
                         Ok(())
                     })
-                    .await??;
+                    .await
+                    .map_err(Error::archive)??;
                 }
                 ArchiveType::Tar => {
                     let dir = dir.to_owned();
-                    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+                    tokio::task::spawn_blocking(move || -> Result<()> {
                         let mut archive = tar::Archive::new(std::io::Cursor::new(file_buffer));
-                        archive.unpack(dir)?;
+                        archive.unpack(dir).map_err(Error::archive)?;
 
                         Ok(())
                     })
-                    .await??;
+                    .await
+                    .map_err(Error::archive)??;
                 }
                 ArchiveType::Gzip => {
                     let dir = dir.to_owned();
-                    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+                    tokio::task::spawn_blocking(move || -> Result<()> {
                         let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
                             std::io::Cursor::new(file_buffer),
                         ));
-                        archive.unpack(dir)?;
+                        archive.unpack(dir).map_err(Error::archive)?;
                         Ok(())
                     })
-                    .await??;
+                    .await
+                    .map_err(Error::archive)??;
                 }
             }
 
@@ -522,3 +1165,32 @@ impl HttpClient {
         }
     }
 }
+
+/// `Idempotency-Key` the request of `resp` was sent with.
+#[cfg(not(target_arch = "wasm32"))]
+fn sent_idempotency_key(resp: &Response) -> Option<String> {
+    resp.extensions()
+        .get::<idempotency::SentKey>()
+        .map(|key| key.0.clone())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn sent_idempotency_key(_resp: &Response) -> Option<String> {
+    None
+}
+
+/// Cuts the total timeout of a request sent again, after waiting `delay`, to
+/// what is left before `deadline`. `false` when nothing would be left.
+#[cfg(not(target_arch = "wasm32"))]
+fn until_deadline(
+    request: &mut Request,
+    deadline: Option<tokio::time::Instant>,
+    delay: Duration,
+) -> bool {
+    let Some(deadline) = deadline else {
+        return true;
+    };
+    let left = deadline.saturating_duration_since(tokio::time::Instant::now() + delay);
+    *request.timeout_mut() = Some(left);
+    !left.is_zero()
+}
@@ -0,0 +1,43 @@
+use reqwest::{NoProxy, Proxy};
+use url::Url;
+
+/// Proxy the requests go through, HTTP and HTTPS targets alike.
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` address of the proxy,
+    /// `socks5h` lets the proxy resolve the target host.
+    pub url: Url,
+    /// Username and password sent to the proxy.
+    pub credentials: Option<(String, String)>,
+    /// Hosts reached without the proxy, in the `NO_PROXY` format:
+    /// `example.com` also covers its subdomains, and IP networks are written `10.0.0.0/8`.
+    pub no_proxy: Option<Vec<String>>,
+}
+
+impl ProxyConfig {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            credentials: None,
+            no_proxy: None,
+        }
+    }
+
+    pub(crate) fn proxy(&self) -> anyhow::Result<Proxy> {
+        let mut url = self.url.clone();
+        if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            anyhow::bail!("unsupported proxy scheme {}", url.scheme());
+        }
+        // credentials in the url work for every scheme, basic_auth only for http proxies
+        if let Some((username, password)) = &self.credentials {
+            url.set_username(username)
+                .and_then(|_| url.set_password(Some(password)))
+                .map_err(|_| anyhow::anyhow!("{} cannot carry credentials", self.url))?;
+        }
+        let no_proxy = self
+            .no_proxy
+            .as_ref()
+            .and_then(|hosts| NoProxy::from_string(&hosts.join(",")));
+        Ok(Proxy::all(url)?.no_proxy(no_proxy))
+    }
+}
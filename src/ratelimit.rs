@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::HeaderMap;
+
+/// Token bucket, `requests` tokens are added every `per`, up to `burst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// `requests` per second, all of them can be sent at once.
+    pub fn per_second(requests: u32) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(1),
+            burst: requests,
+        }
+    }
+}
+
+/// What a call does when no token is left.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RateLimitMode {
+    /// Waits for a token, callers are served in the order they arrived.
+    #[default]
+    Delay,
+    /// Fails with a [`RateLimitedError`].
+    FailFast,
+}
+
+/// Settings of the client side rate limiter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimiterConfig {
+    /// Bucket shared by every request of the client.
+    pub global: Option<RateLimit>,
+    /// Bucket of each host, every host gets its own.
+    pub per_host: Option<RateLimit>,
+    /// Buckets of these hosts, in place of `per_host`.
+    pub hosts: Vec<(String, RateLimit)>,
+    pub mode: RateLimitMode,
+    /// Pauses a host until the reset when `RateLimit-Remaining` or `X-RateLimit-Remaining`
+    /// drops to zero, and never lets its bucket hold more tokens than the server has left.
+    pub honor_headers: bool,
+}
+
+impl RateLimiterConfig {
+    /// Refuses buckets that would never refill or never hold a token.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        let limits = self.global.iter().chain(&self.per_host);
+        for limit in limits.chain(self.hosts.iter().map(|(_, limit)| limit)) {
+            if limit.requests == 0 || limit.per.is_zero() || limit.burst == 0 {
+                anyhow::bail!("rate limit {:?} never lets a request through", limit);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request refused without being sent because it would exceed a rate limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitedError {
+    pub host: String,
+    /// Time before a token is available.
+    pub retry_in: Duration,
+    /// Key of a `POST` or `PATCH` sent with an `Idempotency-Key`.
+    pub idempotency_key: Option<String>,
+}
+
+impl fmt::Display for RateLimitedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rate limit reached for {}, next request allowed in {:?}",
+            self.host, self.retry_in
+        )
+    }
+}
+
+impl std::error::Error for RateLimitedError {}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Goes below zero when delayed calls reserve tokens that are not there yet.
+    tokens: f64,
+    burst: f64,
+    tokens_per_second: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            burst: limit.burst as f64,
+            tokens_per_second: limit.requests as f64 / limit.per.as_secs_f64(),
+            updated: now,
+        }
+    }
+
+    /// Time before a token is available.
+    fn wait(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.tokens_per_second).min(self.burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.tokens_per_second)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HostLimits {
+    bucket: Option<Bucket>,
+    /// Set from the rate limit headers of the last response.
+    paused_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    global: Option<Bucket>,
+    hosts: HashMap<String, HostLimits>,
+}
+
+/// Rate limiter shared by the clones of a client.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: RateLimiterConfig,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimiterConfig) -> Self {
+        let global = config
+            .global
+            .map(|limit| Bucket::new(limit, Instant::now()));
+        Self {
+            config,
+            buckets: Mutex::new(Buckets {
+                global,
+                hosts: HashMap::new(),
+            }),
+        }
+    }
+
+    fn host_limit(&self, host: &str) -> Option<RateLimit> {
+        self.config
+            .hosts
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host))
+            .map(|(_, limit)| *limit)
+            .or(self.config.per_host)
+    }
+
+    fn host_limits<'a>(
+        &self,
+        hosts: &'a mut HashMap<String, HostLimits>,
+        host: &str,
+        now: Instant,
+    ) -> &'a mut HostLimits {
+        hosts.entry(host.to_string()).or_insert_with(|| HostLimits {
+            bucket: self.host_limit(host).map(|limit| Bucket::new(limit, now)),
+            paused_until: None,
+        })
+    }
+
+    /// Takes a token from the global and host buckets, waiting for them in delay mode.
+    pub(crate) async fn acquire(&self, host: &str) -> Result<(), RateLimitedError> {
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let Buckets { global, hosts } = &mut *buckets;
+            let now = Instant::now();
+            let host_limits = self.host_limits(hosts, host, now);
+            let wait = [
+                global.as_mut().map(|bucket| bucket.wait(now)),
+                host_limits.bucket.as_mut().map(|bucket| bucket.wait(now)),
+                host_limits
+                    .paused_until
+                    .map(|until| until.saturating_duration_since(now)),
+            ]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or_default();
+            if self.config.mode == RateLimitMode::FailFast && !wait.is_zero() {
+                return Err(RateLimitedError {
+                    host: host.to_string(),
+                    retry_in: wait,
+                    idempotency_key: None,
+                });
+            }
+            for bucket in [global.as_mut(), host_limits.bucket.as_mut()]
+                .into_iter()
+                .flatten()
+            {
+                bucket.tokens -= 1.0;
+            }
+            wait
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Slows the host down as asked by the rate limit headers of its response.
+    pub(crate) fn record(&self, host: &str, headers: &HeaderMap) {
+        if !self.config.honor_headers {
+            return;
+        }
+        let Some(remaining) = header_number(headers, "ratelimit-remaining") else {
+            return;
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let host_limits = self.host_limits(&mut buckets.hosts, host, now);
+        if let Some(bucket) = &mut host_limits.bucket {
+            // refills the bucket up to now, so the clamp is not undone by the next refill
+            bucket.wait(now);
+            bucket.tokens = bucket.tokens.min(remaining as f64);
+        }
+        host_limits.paused_until = match remaining {
+            0 => header_number(headers, "ratelimit-reset").map(|reset| now + reset_delay(reset)),
+            _ => None,
+        };
+    }
+}
+
+/// Value of the header or of its `x-` variant.
+fn header_number(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .or_else(|| headers.get(format!("x-{}", name)))?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// The reset is in seconds, some servers send a unix timestamp in `X-RateLimit-Reset` instead.
+fn reset_delay(reset: u64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if reset > now / 2 {
+        Duration::from_secs(reset.saturating_sub(now))
+    } else {
+        Duration::from_secs(reset)
+    }
+}
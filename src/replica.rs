@@ -0,0 +1,150 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::{Error, TimeoutPhase};
+
+/// How relative-endpoint calls pick a base url among the replicas.
+///
+/// Whatever the strategy, unhealthy replicas are only tried after the healthy ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplicaStrategy {
+    /// Each call starts from the next replica.
+    RoundRobin,
+    /// Each call starts from the replica with the fewest requests in flight.
+    LeastInFlight,
+    /// Calls go to the first replica, the next ones only take over when it fails.
+    #[default]
+    Failover,
+}
+
+/// Health of a replica, as seen by this client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplicaHealth {
+    pub base_url: Url,
+    /// `false` for the cool-down after a failure.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub in_flight: usize,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Replica {
+    base_url: Url,
+    in_flight: Arc<AtomicUsize>,
+    health: Mutex<Health>,
+}
+
+impl Replica {
+    fn is_healthy(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap();
+        health.unhealthy_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// Request in flight to a replica, stored in the extensions of its response.
+#[derive(Debug)]
+pub(crate) struct ReplicaGuard {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for ReplicaGuard {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Base urls of the replicas with their health, shared by the clones of a client.
+#[derive(Debug)]
+pub(crate) struct Replicas {
+    replicas: Vec<Replica>,
+    strategy: ReplicaStrategy,
+    cool_down: Duration,
+    next: AtomicUsize,
+}
+
+impl Replicas {
+    pub(crate) fn new(base_urls: Vec<Url>, strategy: ReplicaStrategy, cool_down: Duration) -> Self {
+        Self {
+            replicas: base_urls
+                .into_iter()
+                .map(|base_url| Replica {
+                    base_url,
+                    in_flight: Arc::new(AtomicUsize::new(0)),
+                    health: Mutex::new(Health::default()),
+                })
+                .collect(),
+            strategy,
+            cool_down,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Indexes of the replicas in the order a call tries them.
+    pub(crate) fn order(&self) -> Vec<usize> {
+        let count = self.replicas.len();
+        let mut order = (0..count).collect::<Vec<_>>();
+        match self.strategy {
+            ReplicaStrategy::RoundRobin => {
+                order.rotate_left(self.next.fetch_add(1, Ordering::Relaxed) % count)
+            }
+            ReplicaStrategy::LeastInFlight => {
+                order.sort_by_key(|index| self.replicas[*index].in_flight.load(Ordering::Relaxed))
+            }
+            ReplicaStrategy::Failover => {}
+        }
+        let now = Instant::now();
+        order.sort_by_key(|index| !self.replicas[*index].is_healthy(now));
+        order
+    }
+
+    pub(crate) fn base_url(&self, index: usize) -> &Url {
+        &self.replicas[index].base_url
+    }
+
+    pub(crate) fn start(&self, index: usize) -> ReplicaGuard {
+        let in_flight = self.replicas[index].in_flight.clone();
+        in_flight.fetch_add(1, Ordering::Relaxed);
+        ReplicaGuard { in_flight }
+    }
+
+    pub(crate) fn record(&self, index: usize, success: bool) {
+        let mut health = self.replicas[index].health.lock().unwrap();
+        if success {
+            *health = Health::default();
+        } else {
+            health.consecutive_failures += 1;
+            health.unhealthy_until = Some(Instant::now() + self.cool_down);
+        }
+    }
+
+    pub(crate) fn health(&self) -> Vec<ReplicaHealth> {
+        let now = Instant::now();
+        self.replicas
+            .iter()
+            .map(|replica| ReplicaHealth {
+                base_url: replica.base_url.clone(),
+                healthy: replica.is_healthy(now),
+                consecutive_failures: replica.health.lock().unwrap().consecutive_failures,
+                in_flight: replica.in_flight.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// The replica could not be reached, another one may well be up.
+pub(crate) fn is_connection_error(err: &Error) -> bool {
+    match err {
+        Error::Timeout(timeout_err) => timeout_err.phase == TimeoutPhase::Connect,
+        Error::Connect { .. } | Error::CircuitOpen(_) => true,
+        _ => false,
+    }
+}
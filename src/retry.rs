@@ -0,0 +1,174 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+
+use crate::Error;
+
+/// Failure of an attempt that can be retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryErrorKind {
+    /// The connection could not be opened.
+    Connect,
+    /// Any [`TimeoutError`](crate::TimeoutError).
+    Timeout,
+    /// The connection failed while sending the request or waiting for the response.
+    Request,
+}
+
+/// When and how often a request is sent again.
+///
+/// Requests with an idempotent method (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS`)
+/// are retried, `POST` and `PATCH` only with `retry_non_idempotent`. Requests
+/// whose body is a stream, like multipart uploads, are sent once.
+///
+/// All attempts share the [`Timeouts::total`](crate::Timeouts::total) deadline,
+/// no retry starts when its backoff would end past it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, `1` never retries.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every following one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Waits a random time between zero and the backoff, so clients do not retry in lockstep.
+    pub jitter: bool,
+    pub retry_statuses: Vec<StatusCode>,
+    pub retry_errors: Vec<RetryErrorKind>,
+    pub retry_non_idempotent: bool,
+    /// Waits as long as the server asks with `Retry-After` on 429 and 503, in place of the backoff.
+    /// A wait longer than `max_backoff` is not retried, the response is returned.
+    pub honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            retry_statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_errors: vec![RetryErrorKind::Connect, RetryErrorKind::Timeout],
+            retry_non_idempotent: false,
+            honor_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn applies_to(&self, method: &Method) -> bool {
+        self.max_attempts > 1 && (self.retry_non_idempotent || method.is_idempotent())
+    }
+
+    pub(crate) fn retries_status(&self, status: StatusCode) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    pub(crate) fn retries_error(&self, err: &Error) -> bool {
+        error_kind(err).is_some_and(|kind| self.retry_errors.contains(&kind))
+    }
+
+    /// Wait before the attempt after `attempt`, counted from 1.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_backoff);
+        if self.jitter {
+            backoff.mul_f64(fastrand::f64())
+        } else {
+            backoff
+        }
+    }
+
+    /// Wait before retrying a response with a retried status, `None` when the
+    /// server asks for a wait longer than `max_backoff`.
+    pub(crate) fn status_delay(
+        &self,
+        attempt: u32,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<Duration> {
+        match self.retry_after(status, headers) {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Wait asked by the server, if the status allows it.
+    fn retry_after(&self, status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+        if !self.honor_retry_after
+            || !matches!(
+                status,
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+            )
+        {
+            return None;
+        }
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let at = parse_http_date(value)?;
+        Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+}
+
+fn error_kind(err: &Error) -> Option<RetryErrorKind> {
+    match err {
+        Error::Timeout(_) => Some(RetryErrorKind::Timeout),
+        Error::Connect { .. } => Some(RetryErrorKind::Connect),
+        Error::Request { source, .. } if source.is_request() => Some(RetryErrorKind::Request),
+        _ => None,
+    }
+}
+
+/// Parses the IMF-fixdate form, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, the one servers must send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let (_, date) = value.split_once(", ")?;
+    let mut parts = date.split(' ');
+    let day = parts.next()?.parse::<u64>().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year = parts.next()?.parse::<u64>().ok()?;
+    let mut time = parts
+        .next()?
+        .split(':')
+        .map(|part| part.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next()? != "GMT" || year < 1970 || !(1..=31).contains(&day) {
+        return None;
+    }
+    // days from the civil date, counting years from March so the leap day comes last
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era_days = year * 365 + year / 4 - year / 100 + year / 400;
+    let days = era_days + (153 * month + 2) / 5 + day - 1 - 719_468;
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
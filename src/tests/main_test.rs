@@ -4,21 +4,27 @@ mod tests {
     use url::Url;
 
     const TEST_URL: &str = "localhost:3000";
+    #[cfg(feature = "tls-core")]
+    const TEST_MTLS_URL: &str = "localhost:3001";
+    #[cfg(feature = "tls-core")]
+    const TEST_PROXY_URL: &str = "http://localhost:3002";
+    #[cfg(unix)]
+    const TEST_UNIX_SOCKET: &str = "/tmp/simplerusthttpsclient-test.sock";
 
     #[tokio::test]
-    #[cfg(not(feature = "tls"))]
+    #[cfg(not(feature = "tls-core"))]
     async fn test_http() {
         let url_string = String::from("http://") + TEST_URL;
 
         let client = crate::HttpClient::new(Url::parse(&url_string).unwrap(), None);
 
-        let response: anyhow::Result<crate::OkJson> = client.get("/", None).await;
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
 
         assert!(response.is_ok());
     }
 
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(feature = "tls-core")]
     async fn test_https_insecure() {
         let url_string = String::from("https://") + TEST_URL;
 
@@ -27,16 +33,17 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: Some(true),
                 private_chain_bytes: None,
+                ..Default::default()
             }),
             None,
         );
 
-        let response: anyhow::Result<crate::OkJson> = client.get_json("/", None).await;
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
 
         assert!(response.is_ok());
     }
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(feature = "tls-core")]
     async fn test_https_insecure_fail() {
         let url_string = String::from("https://") + TEST_URL;
 
@@ -45,16 +52,42 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: None,
                 private_chain_bytes: None,
+                ..Default::default()
             }),
             None,
         );
 
-        let response: anyhow::Result<crate::OkJson> = client.get_json("/", None).await;
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
 
         assert!(response.is_err());
     }
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_insecure_trusted_hosts() {
+        let tls_config = crate::TlsConfig {
+            insecure: Some(true),
+            trusted_hosts: Some(vec!["127.0.0.0/8".into()]),
+            ..Default::default()
+        };
+
+        let in_scope = crate::HttpClient::new(
+            Url::parse("https://127.0.0.1:3000").unwrap(),
+            Some(tls_config.clone()),
+            None,
+        );
+        let response: crate::Result<crate::OkJson> = in_scope.get_json("/", None).await;
+        assert!(response.is_ok());
+
+        let out_of_scope = crate::HttpClient::new(
+            Url::parse(&(String::from("https://") + TEST_URL)).unwrap(),
+            Some(tls_config),
+            None,
+        );
+        let response: crate::Result<crate::OkJson> = out_of_scope.get_json("/", None).await;
+        assert!(response.is_err());
+    }
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
     async fn test_https_private_tls() {
         let url_string = String::from("https://") + TEST_URL;
 
@@ -65,17 +98,1400 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: Some(false),
                 private_chain_bytes: Some(my_cert_bytes.to_vec()),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "tls-core")]
+    fn test_builder_invalid_private_chain() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::builder(Url::parse(&url_string).unwrap())
+            .tls_config(crate::TlsConfig {
+                insecure: Some(false),
+                private_chain_bytes: Some(
+                    b"-----BEGIN CERTIFICATE-----\nnot base64!\n-----END CERTIFICATE-----\n"
+                        .to_vec(),
+                ),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(client.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_mtls_pem() {
+        let url_string = String::from("https://") + TEST_MTLS_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                client_identity: Some(crate::ClientIdentity::Pem {
+                    cert_chain: include_bytes!("nodeserver/client_cert.pem").to_vec(),
+                    private_key: include_bytes!("nodeserver/client_key.pem").to_vec(),
+                }),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_mtls_pkcs12() {
+        let url_string = String::from("https://") + TEST_MTLS_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                client_identity: Some(crate::ClientIdentity::Pkcs12 {
+                    der: include_bytes!("nodeserver/client.p12").to_vec(),
+                    password: String::from("test"),
+                }),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_mtls_missing_identity_fail() {
+        let url_string = String::from("https://") + TEST_MTLS_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "tls-core")]
+    fn test_builder_pkcs12_wrong_password() {
+        let url_string = String::from("https://") + TEST_MTLS_URL;
+
+        let client = crate::HttpClient::builder(Url::parse(&url_string).unwrap())
+            .tls_config(crate::TlsConfig {
+                client_identity: Some(crate::ClientIdentity::Pkcs12 {
+                    der: include_bytes!("nodeserver/client.p12").to_vec(),
+                    password: String::from("wrong"),
+                }),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(client.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_host_configs() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                host_configs: Some(vec![(
+                    "127.0.0.1".into(),
+                    crate::TlsConfig {
+                        insecure: Some(true),
+                        client_identity: Some(crate::ClientIdentity::Pem {
+                            cert_chain: include_bytes!("nodeserver/client_cert.pem").to_vec(),
+                            private_key: include_bytes!("nodeserver/client_key.pem").to_vec(),
+                        }),
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+        assert!(response.is_ok());
+
+        let file_buffer = client
+            .get_file_buffer(Url::parse("https://127.0.0.1:3001/").unwrap(), None)
+            .await;
+        assert!(file_buffer.unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "native-tls")]
+    fn test_native_tls_unsupported_option() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let result = crate::HttpClient::builder(Url::parse(&url_string).unwrap())
+            .tls_config(crate::TlsConfig {
+                pins: Some(Vec::new()),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_system_roots() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        // stands in for a corporate CA pushed into the system bundle
+        std::env::set_var(
+            "SSL_CERT_FILE",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/tests/nodeserver/ca_cert.pem"
+            ),
+        );
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                system_roots: Some(crate::SystemRoots::Only),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_crl() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                revocation: Some(crate::RevocationCheck {
+                    crls: vec![crate::CrlSource::Pem(
+                        include_bytes!("nodeserver/ca_crl.pem").to_vec(),
+                    )],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_crl_revoked() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                revocation: Some(crate::RevocationCheck {
+                    crls: vec![crate::CrlSource::Der(
+                        include_bytes!("nodeserver/server_revoked_crl.der").to_vec(),
+                    )],
+                    end_entity_only: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_ca_bundle() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        // the CA is not the first certificate of the bundle
+        let mut bundle = include_bytes!("nodeserver/client_cert.pem").to_vec();
+        bundle.extend_from_slice(include_bytes!("nodeserver/ca_cert.pem"));
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                ca_sources: Some(vec![crate::CaSource::Pem(bundle)]),
+                private_roots_only: Some(true),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_ca_dir() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let ca_dir = tempfile::tempdir().unwrap();
+        let ca_der =
+            rustls_pemfile::certs(&mut include_bytes!("nodeserver/ca_cert.pem").as_slice())
+                .next()
+                .unwrap()
+                .unwrap();
+        std::fs::write(ca_dir.path().join("ca_cert.der"), ca_der.as_ref()).unwrap();
+        std::fs::write(ca_dir.path().join("README"), b"not a certificate").unwrap();
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                ca_sources: Some(vec![crate::CaSource::Dir(ca_dir.path().to_path_buf())]),
+                private_roots_only: Some(true),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_private_roots_only_fail() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                ca_sources: Some(vec![crate::CaSource::Pem(
+                    include_bytes!("nodeserver/client_cert.pem").to_vec(),
+                )]),
+                private_roots_only: Some(true),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "tls-core")]
+    fn test_builder_private_roots_only_without_ca() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::builder(Url::parse(&url_string).unwrap())
+            .tls_config(crate::TlsConfig {
+                private_roots_only: Some(true),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(client.is_err());
+    }
+
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    fn server_pin() -> crate::PinHash {
+        let server_cert =
+            rustls_pemfile::certs(&mut include_bytes!("nodeserver/server_cert.pem").as_slice())
+                .next()
+                .unwrap()
+                .unwrap();
+        crate::PinHash::spki_of(&server_cert).unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_pinned() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                pins: Some(vec![crate::CertificatePin {
+                    host: "localhost".into(),
+                    hashes: vec![server_pin()],
+                }]),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_pin_mismatch() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                pins: Some(vec![
+                    crate::CertificatePin {
+                        host: "localhost".into(),
+                        hashes: vec![crate::PinHash::Spki([0; 32])],
+                    },
+                    crate::CertificatePin {
+                        host: "*.example.com".into(),
+                        hashes: vec![server_pin()],
+                    },
+                ]),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        let err = response.unwrap_err();
+        let crate::Error::Tls { source, .. } = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        let pin_err = source.downcast_ref::<crate::PinMismatchError>().unwrap();
+        assert_eq!(pin_err.host, "localhost");
+        assert_eq!(crate::PinHash::Spki(pin_err.seen_spki), server_pin());
+    }
+
+    /// Trusts the test server certificate by fingerprint, like an inventory lookup would.
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    #[derive(Debug)]
+    struct InventoryVerifier {
+        known: crate::PinHash,
+    }
+
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    impl crate::CertificateVerifier for InventoryVerifier {
+        fn verify(&self, server: &crate::ServerCertificate<'_>) -> crate::CertificateVerdict {
+            assert_eq!(
+                server.default_result,
+                Err(crate::CertificateError::UnknownIssuer)
+            );
+            if crate::PinHash::certificate_of(server.end_entity) == self.known {
+                crate::CertificateVerdict::Accept
+            } else {
+                crate::CertificateVerdict::Reject(String::from("not in inventory"))
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_custom_verifier() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let server_cert =
+            rustls_pemfile::certs(&mut include_bytes!("nodeserver/server_cert.pem").as_slice())
+                .next()
+                .unwrap()
+                .unwrap();
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                verifier: Some(std::sync::Arc::new(InventoryVerifier {
+                    known: crate::PinHash::certificate_of(&server_cert),
+                })),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_custom_verifier_reject() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                verifier: Some(std::sync::Arc::new(InventoryVerifier {
+                    known: crate::PinHash::Certificate([0; 32]),
+                })),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        let err = response.unwrap_err();
+        let crate::Error::Tls { source, .. } = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        let rejected = source
+            .downcast_ref::<crate::CertificateRejectedError>()
+            .unwrap();
+        assert_eq!(rejected.host, "localhost");
+        assert_eq!(rejected.reason, "not in inventory");
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_tls13_only() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                min_version: Some(crate::TlsVersion::Tls13),
+                cipher_suites: Some(vec![String::from("TLS13_AES_256_GCM_SHA384")]),
+                alpn_protocols: Some(vec![String::from("http/1.1")]),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "tls-core")]
+    fn test_builder_tls_misconfiguration() {
+        let url = Url::parse(&(String::from("https://") + TEST_URL)).unwrap();
+
+        let invalid_configs = [
+            crate::TlsConfig {
+                min_version: Some(crate::TlsVersion::Tls13),
+                max_version: Some(crate::TlsVersion::Tls12),
+                ..Default::default()
+            },
+            crate::TlsConfig {
+                max_version: Some(crate::TlsVersion::Tls12),
+                cipher_suites: Some(vec![String::from("TLS13_AES_256_GCM_SHA384")]),
+                ..Default::default()
+            },
+            crate::TlsConfig {
+                cipher_suites: Some(vec![String::from("TLS_RSA_WITH_RC4_128_MD5")]),
+                ..Default::default()
+            },
+            crate::TlsConfig {
+                alpn_protocols: Some(vec![String::from("spdy/3")]),
+                ..Default::default()
+            },
+        ];
+
+        for tls_config in invalid_configs {
+            let client = crate::HttpClient::builder(url.clone())
+                .tls_config(tls_config)
+                .build();
+
+            assert!(client.is_err());
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_response_tls_details() {
+        use crate::ResponseTlsExt;
+
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                ..Default::default()
             }),
             None,
         );
 
-        let response: anyhow::Result<crate::OkJson> = client.get_json("/", None).await;
+        let response = client.get("/", None).await.unwrap();
+
+        let details = response.tls_details().unwrap();
+        assert_eq!(details.http_version, reqwest::Version::HTTP_11);
+        assert_eq!(details.alpn_protocol, None);
+        let peer_certificate = details.peer_certificate.unwrap();
+        assert_eq!(peer_certificate.subject, "CN=localhost");
+        assert_eq!(peer_certificate.issuer, "CN=MyCA");
+        assert_eq!(peer_certificate.subject_alt_names, vec!["localhost"]);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_inspect_tls() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                ..Default::default()
+            }),
+            None,
+        );
 
+        let report = client.inspect_tls("/").await.unwrap();
+
+        assert_eq!(report.protocol_version, Some(crate::TlsVersion::Tls13));
+        assert!(report.cipher_suite.is_some());
+        assert_eq!(report.peer_certificate.subject, "CN=localhost");
+        assert!(report.expires_in().is_some());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_inspect_tls_timeout() {
+        // accepts connections and never answers the handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        let client = crate::HttpClient::new(
+            Url::parse(&format!("https://127.0.0.1:{}", port)).unwrap(),
+            None,
+            None,
+        );
+
+        let err = client
+            .with_timeouts(crate::Timeouts {
+                read: Some(std::time::Duration::from_millis(200)),
+                ..Default::default()
+            })
+            .inspect_tls("/")
+            .await
+            .unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Read);
+
+        let err = client
+            .with_timeouts(crate::Timeouts {
+                total: Some(std::time::Duration::from_millis(200)),
+                ..Default::default()
+            })
+            .inspect_tls("/")
+            .await
+            .unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Total);
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_reload_tls_material() {
+        let url_string = String::from("https://") + TEST_MTLS_URL;
+
+        let tls_dir = tempfile::tempdir().unwrap();
+        let cert_path = tls_dir.path().join("client_cert.pem");
+        let key_path = tls_dir.path().join("client_key.pem");
+        let ca_path = tls_dir.path().join("ca_cert.pem");
+        std::fs::write(&cert_path, include_bytes!("nodeserver/client_cert.pem")).unwrap();
+        std::fs::write(&key_path, include_bytes!("nodeserver/client_key.pem")).unwrap();
+        // start with a CA that did not sign the server certificate
+        std::fs::write(&ca_path, include_bytes!("nodeserver/client_cert.pem")).unwrap();
+
+        let source = std::sync::Arc::new(
+            crate::FileTlsSource::new()
+                .client_identity(cert_path, key_path)
+                .ca_file(ca_path.clone()),
+        );
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_roots_only: Some(true),
+                material_source: Some(source.clone()),
+                ..Default::default()
+            }),
+            None,
+        );
+        let cloned_client = client.clone();
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+        assert!(response.is_err());
+
+        // a half-written file is reported, and read again on the next handshake
+        // even when the complete one keeps the same modification time
+        let modified = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        let write_ca = |pem: &[u8]| {
+            std::fs::write(&ca_path, pem).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&ca_path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        write_ca(b"-----BEGIN CERTIFICATE-----\nMIIB");
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+        assert!(response.is_err());
+        assert!(source.last_error().is_some());
+
+        write_ca(include_bytes!("nodeserver/ca_cert.pem"));
+        let response: crate::Result<crate::OkJson> = cloned_client.get_json("/", None).await;
         assert!(response.is_ok());
+        assert_eq!(source.last_error(), None);
     }
 
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(all(feature = "tls-core", not(feature = "native-tls")))]
+    async fn test_https_key_log() {
+        let url_string = String::from("https://") + TEST_URL;
+
+        let key_log_dir = tempfile::tempdir().unwrap();
+        let key_log_path = key_log_dir.path().join("sslkeylog.txt");
+
+        let client = crate::HttpClient::new(
+            Url::parse(&url_string).unwrap(),
+            Some(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                key_log: Some(crate::KeyLogDestination::File(key_log_path.clone())),
+                ..Default::default()
+            }),
+            None,
+        );
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+        assert!(response.is_ok());
+
+        let key_log = std::fs::read_to_string(key_log_path).unwrap();
+        assert!(key_log
+            .lines()
+            .any(|line| line.starts_with("CLIENT_TRAFFIC_SECRET_0 ")));
+    }
+
+    #[cfg(feature = "tls-core")]
+    fn proxied_client(proxy: crate::ProxyConfig) -> crate::HttpClient {
+        let url_string = String::from("https://") + TEST_URL;
+
+        crate::HttpClient::builder(Url::parse(&url_string).unwrap())
+            .tls_config(crate::TlsConfig {
+                private_chain_bytes: Some(include_bytes!("nodeserver/ca_cert.pem").to_vec()),
+                ..Default::default()
+            })
+            .proxy(proxy)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_proxy() {
+        let client = proxied_client(crate::ProxyConfig {
+            credentials: Some((String::from("proxyuser"), String::from("proxypass"))),
+            ..crate::ProxyConfig::new(Url::parse(TEST_PROXY_URL).unwrap())
+        });
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+        assert!(response.is_ok());
+
+        let file_url = Url::parse(&(String::from("https://") + TEST_URL)).unwrap();
+        let file_buffer = client.get_file_buffer(file_url, None).await;
+        assert!(file_buffer.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_proxy_auth_fail() {
+        let client = proxied_client(crate::ProxyConfig::new(Url::parse(TEST_PROXY_URL).unwrap()));
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_https_no_proxy() {
+        // nothing listens there, the request only succeeds if the proxy is bypassed
+        let client = proxied_client(crate::ProxyConfig {
+            no_proxy: Some(vec![String::from("localhost")]),
+            ..crate::ProxyConfig::new(Url::parse("http://localhost:9").unwrap())
+        });
+
+        let response: crate::Result<crate::OkJson> = client.get_json("/", None).await;
+
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_unix_socket() {
+        let client = crate::HttpClient::builder(
+            Url::parse(&(String::from("unix://") + TEST_UNIX_SOCKET)).unwrap(),
+        )
+        .default_header(
+            reqwest::header::HeaderName::from_static("x-agent-token"),
+            reqwest::header::HeaderValue::from_static("test"),
+        )
+        .build()
+        .unwrap();
+
+        let response: crate::OkJson = client.get_json("/", None).await.unwrap();
+        assert!(response.ok);
+
+        // the host of the url does not matter, every request goes over the socket
+        let file_buffer = client
+            .get_file_buffer(Url::parse("http://agent/file").unwrap(), None)
+            .await;
+        assert!(file_buffer.unwrap().is_some());
+    }
+
+    #[cfg(feature = "tls-core")]
+    fn insecure_client() -> crate::HttpClient {
+        crate::HttpClient::new(
+            Url::parse(&(String::from("https://") + TEST_URL)).unwrap(),
+            Some(crate::TlsConfig {
+                insecure: Some(true),
+                ..Default::default()
+            }),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_read_timeout() {
+        let client = insecure_client().with_timeouts(crate::Timeouts {
+            read: Some(std::time::Duration::from_millis(300)),
+            ..Default::default()
+        });
+
+        let err = client.get("/stall", None).await.unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Read);
+
+        // the head arrives in time, the rest of the body does not
+        let err = client
+            .get_json::<crate::OkJson>("/stall-body", None)
+            .await
+            .unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Read);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_total_timeout() {
+        let client =
+            crate::HttpClient::builder(Url::parse(&(String::from("https://") + TEST_URL)).unwrap())
+                .tls_config(crate::TlsConfig {
+                    insecure: Some(true),
+                    ..Default::default()
+                })
+                .timeouts(crate::Timeouts {
+                    total: Some(std::time::Duration::from_millis(300)),
+                    ..Default::default()
+                })
+                .build()
+                .unwrap();
+
+        let err = client
+            .get_file_buffer(
+                Url::parse(&(String::from("https://") + TEST_URL + "/stall-body")).unwrap(),
+                None,
+            )
+            .await
+            .unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Total);
+
+        // a per-call override wins over the client default
+        let response: crate::OkJson = client
+            .with_timeouts(crate::Timeouts {
+                total: Some(std::time::Duration::from_secs(5)),
+                ..Default::default()
+            })
+            .get_json("/", None)
+            .await
+            .unwrap();
+        assert!(response.ok);
+    }
+
+    /// The node server counts requests per /flaky/ path, a new path per run keeps tests repeatable.
+    #[cfg(feature = "tls-core")]
+    fn flaky_path(name: &str) -> String {
+        let run = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        format!("/flaky/{}-{}", name, run)
+    }
+
+    #[cfg(feature = "tls-core")]
+    fn fast_retries() -> crate::RetryPolicy {
+        crate::RetryPolicy {
+            initial_backoff: std::time::Duration::from_millis(10),
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_retry_total_timeout() {
+        let client = insecure_client()
+            .with_retry_policy(Some(fast_retries()))
+            .with_timeouts(crate::Timeouts {
+                total: Some(std::time::Duration::from_millis(300)),
+                ..Default::default()
+            });
+
+        // the timed out attempt is retried, within the same deadline
+        let started = std::time::Instant::now();
+        let err = client.get("/stall", None).await.unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_millis(600));
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Total);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_retry() {
+        let client = insecure_client().with_retry_policy(Some(fast_retries()));
+
+        let response: crate::OkJson = client.get_json(&flaky_path("get"), None).await.unwrap();
+        assert!(response.ok);
+
+        // post is only retried when asked for
+        let post_path = flaky_path("post");
+        let response = client.post(&post_path, &(), None).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = client
+            .with_retry_policy(Some(crate::RetryPolicy {
+                retry_non_idempotent: true,
+                ..fast_retries()
+            }))
+            .post(&post_path, &(), None)
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+
+        let response = client
+            .with_retry_policy(None)
+            .get(&flaky_path("once"), None)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_status_error() {
+        let client = insecure_client();
+        let url = client.base_url.join(&flaky_path("download")).unwrap();
+
+        let err = client.get_file_buffer(url.clone(), None).await.unwrap_err();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        let crate::Error::Status(status_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(status_err.url, url);
+        assert_eq!(status_err.body, r#"{"error":"try again"}"#);
+        assert_eq!(status_err.headers["content-type"], "application/json");
+    }
+
+    #[cfg(feature = "tls-core")]
+    #[derive(Debug, serde::Deserialize)]
+    struct ErrorJson {
+        error: String,
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_json_status() {
+        let client = insecure_client();
+        let path = flaky_path("json");
+
+        // the error body is not decoded as the success type
+        let err = client
+            .get_json::<crate::OkJson>(&path, None)
+            .await
+            .unwrap_err();
+        let crate::Error::Status(status_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(status_err.status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status_err.body, r#"{"error":"try again"}"#);
+
+        let err = client
+            .get_json_with_error::<crate::OkJson, ErrorJson>(&path, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(err.payload().unwrap().error, "try again");
+
+        let response = client
+            .get_json_with_error::<crate::OkJson, ErrorJson>(&path, None)
+            .await
+            .unwrap();
+        assert!(response.ok);
+
+        // an error body that is not an `E` is still a status error
+        let err = client
+            .get_json_with_error::<crate::OkJson, crate::OkJson>(&flaky_path("json-other"), None)
+            .await
+            .unwrap_err();
+        let crate::ApiError::Client(crate::Error::Status(status_err)) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(status_err.body, r#"{"error":"try again"}"#);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_retry_after() {
+        let client = insecure_client().with_retry_policy(Some(fast_retries()));
+
+        let started = std::time::Instant::now();
+        let response = client
+            .get(&(flaky_path("after") + "?retry_after=1"), None)
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert!(started.elapsed() >= std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_retry_after_cap() {
+        let policy = crate::RetryPolicy {
+            max_backoff: std::time::Duration::from_secs(5),
+            ..Default::default()
+        };
+        let status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
+        let mut headers = reqwest::header::HeaderMap::new();
+
+        headers.insert(reqwest::header::RETRY_AFTER, "5".parse().unwrap());
+        assert_eq!(
+            policy.status_delay(1, status, &headers),
+            Some(std::time::Duration::from_secs(5))
+        );
+
+        // a wait over the cap is not retried, the response goes back to the caller
+        headers.insert(reqwest::header::RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(policy.status_delay(1, status, &headers), None);
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Fri, 31 Dec 9999 23:59:59 GMT".parse().unwrap(),
+        );
+        assert_eq!(policy.status_delay(1, status, &headers), None);
+    }
+
+    #[derive(serde::Deserialize)]
+    #[cfg(feature = "tls-core")]
+    struct FlakyJson {
+        idempotency_keys: Vec<String>,
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_idempotency_key() {
+        let client =
+            crate::HttpClient::builder(Url::parse(&(String::from("https://") + TEST_URL)).unwrap())
+                .tls_config(crate::TlsConfig {
+                    insecure: Some(true),
+                    ..Default::default()
+                })
+                .retry_policy(crate::RetryPolicy {
+                    retry_non_idempotent: true,
+                    ..fast_retries()
+                })
+                .generate_idempotency_keys(true)
+                .build()
+                .unwrap();
+
+        // every attempt of a call carries the same key
+        let response: FlakyJson = client
+            .post_json(&flaky_path("post"), &(), None)
+            .await
+            .unwrap();
+        assert_eq!(response.idempotency_keys.len(), 3);
+        assert_eq!(response.idempotency_keys[0].len(), 36);
+        assert!(response
+            .idempotency_keys
+            .iter()
+            .all(|key| *key == response.idempotency_keys[0]));
+
+        let other: FlakyJson = client
+            .patch_json(&flaky_path("patch"), &(), None)
+            .await
+            .unwrap();
+        assert_ne!(other.idempotency_keys[0], response.idempotency_keys[0]);
+
+        let err = client
+            .with_idempotency_key("order-42")
+            .with_timeouts(crate::Timeouts {
+                read: Some(std::time::Duration::from_millis(200)),
+                ..Default::default()
+            })
+            .post("/stall", &(), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.idempotency_key(), Some("order-42"));
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.idempotency_key.as_deref(), Some("order-42"));
+
+        // a 5xx left after the retries keeps the key to reconcile the call
+        let path = flaky_path("post-5xx");
+        let with_single_retry = client.with_retry_policy(Some(crate::RetryPolicy {
+            max_attempts: 2,
+            retry_non_idempotent: true,
+            ..fast_retries()
+        }));
+        let err = with_single_retry
+            .with_idempotency_key("order-43")
+            .post_json::<crate::OkJson, _>(&path, &(), None)
+            .await
+            .unwrap_err();
+        // the key does not hide the failure from a match
+        assert_eq!(err.idempotency_key(), Some("order-43"));
+        let crate::Error::Status(status_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(status_err.status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status_err.idempotency_key.as_deref(), Some("order-43"));
+
+        let err = client
+            .with_retry_policy(None)
+            .with_idempotency_key("order-44")
+            .post_json_with_error::<crate::OkJson, ErrorJson, _>(&flaky_path("post-api"), &(), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.idempotency_key(), Some("order-44"));
+        assert_eq!(err.payload().unwrap().error, "try again");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_circuit_breaker() {
+        let client =
+            crate::HttpClient::builder(Url::parse(&(String::from("https://") + TEST_URL)).unwrap())
+                .tls_config(crate::TlsConfig {
+                    insecure: Some(true),
+                    ..Default::default()
+                })
+                .circuit_breaker(crate::CircuitBreakerConfig {
+                    failure_threshold: 2,
+                    cool_down: std::time::Duration::from_millis(300),
+                })
+                .build()
+                .unwrap();
+        let path = flaky_path("circuit");
+
+        // two 503 in a row open the circuit, clones share it
+        for _ in 0..2 {
+            let response = client.get(&path, None).await.unwrap();
+            assert!(response.status().is_server_error());
+        }
+        assert_eq!(
+            client.clone().circuit_state("localhost"),
+            Some(crate::CircuitState::Open)
+        );
+        assert_eq!(
+            client.circuit_states(),
+            vec![(String::from("localhost"), crate::CircuitState::Open)]
+        );
+        let err = client.get(&path, None).await.unwrap_err();
+        let crate::Error::CircuitOpen(open_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(open_err.host, "localhost");
+
+        // after the cool-down a probe goes through and closes it again
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert_eq!(
+            client.circuit_state("localhost"),
+            Some(crate::CircuitState::HalfOpen)
+        );
+        let response = client.get(&path, None).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(
+            client.circuit_state("localhost"),
+            Some(crate::CircuitState::Closed)
+        );
+    }
+
+    #[cfg(feature = "tls-core")]
+    fn rate_limited_client(config: crate::RateLimiterConfig) -> crate::HttpClient {
+        crate::HttpClient::builder(Url::parse(&(String::from("https://") + TEST_URL)).unwrap())
+            .tls_config(crate::TlsConfig {
+                insecure: Some(true),
+                ..Default::default()
+            })
+            .rate_limiter(config)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_builder_empty_rate_limit() {
+        let client = crate::HttpClient::builder(Url::parse("http://localhost").unwrap())
+            .rate_limiter(crate::RateLimiterConfig {
+                hosts: vec![(String::from("localhost"), crate::RateLimit::per_second(0))],
+                ..Default::default()
+            })
+            .build();
+
+        assert!(matches!(client, Err(crate::Error::Config(_))));
+
+        let client = crate::HttpClient::builder(Url::parse("http://localhost").unwrap())
+            .rate_limiter(crate::RateLimiterConfig {
+                per_host: Some(crate::RateLimit {
+                    burst: 0,
+                    ..crate::RateLimit::per_second(5)
+                }),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(matches!(client, Err(crate::Error::Config(_))));
+    }
+
+    #[test]
+    fn test_builder_empty_concurrency_limits() {
+        let client = crate::HttpClient::builder(Url::parse("http://localhost").unwrap())
+            .concurrency_limits(crate::ConcurrencyLimits {
+                max_in_flight_per_host: Some(0),
+                ..Default::default()
+            })
+            .build();
+
+        assert!(matches!(client, Err(crate::Error::Config(_))));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_rate_limit() {
+        let client = rate_limited_client(crate::RateLimiterConfig {
+            per_host: Some(crate::RateLimit {
+                burst: 1,
+                ..crate::RateLimit::per_second(4)
+            }),
+            ..Default::default()
+        });
+
+        // the first call takes the only token, the next ones wait 250ms each
+        let started = std::time::Instant::now();
+        for _ in 0..3 {
+            let response: crate::OkJson = client.clone().get_json("/", None).await.unwrap();
+            assert!(response.ok);
+        }
+        assert!(started.elapsed() >= std::time::Duration::from_millis(500));
+
+        let client = rate_limited_client(crate::RateLimiterConfig {
+            global: Some(crate::RateLimit {
+                burst: 1,
+                ..crate::RateLimit::per_second(1)
+            }),
+            mode: crate::RateLimitMode::FailFast,
+            ..Default::default()
+        });
+        client.get("/", None).await.unwrap();
+        let err = client.get("/", None).await.unwrap_err();
+        let crate::Error::RateLimited(limited_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert!(limited_err.retry_in > std::time::Duration::ZERO);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_rate_limit_headers() {
+        let client = rate_limited_client(crate::RateLimiterConfig {
+            mode: crate::RateLimitMode::FailFast,
+            honor_headers: true,
+            ..Default::default()
+        });
+
+        client.get("/ratelimited", None).await.unwrap();
+        let err = client.get("/", None).await.unwrap_err();
+        assert!(matches!(err, crate::Error::RateLimited(_)));
+
+        // the quota is back after the reset
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let response: crate::OkJson = client.get_json("/", None).await.unwrap();
+        assert!(response.ok);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_concurrency_limits() {
+        let client =
+            crate::HttpClient::builder(Url::parse(&(String::from("https://") + TEST_URL)).unwrap())
+                .tls_config(crate::TlsConfig {
+                    insecure: Some(true),
+                    ..Default::default()
+                })
+                .concurrency_limits(crate::ConcurrencyLimits {
+                    max_in_flight_per_host: Some(1),
+                    ..Default::default()
+                })
+                .timeouts(crate::Timeouts {
+                    total: Some(std::time::Duration::from_millis(300)),
+                    ..Default::default()
+                })
+                .build()
+                .unwrap();
+
+        // the response holds the only slot of the host until it is dropped,
+        // the wait of a clone for it counts toward its total timeout
+        let response = client.get("/", None).await.unwrap();
+        let err = client.clone().get("/", None).await.unwrap_err();
+        let crate::Error::Timeout(timeout_err) = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert_eq!(timeout_err.phase, crate::TimeoutPhase::Total);
+
+        let waiting = tokio::spawn({
+            let client = client.clone();
+            async move { client.get_json::<crate::OkJson>("/", None).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        drop(response);
+        assert!(waiting.await.unwrap().unwrap().ok);
+    }
+
+    #[cfg(feature = "tls-core")]
+    fn replicated_client(
+        base_urls: &[&str],
+        strategy: crate::ReplicaStrategy,
+    ) -> crate::HttpClient {
+        let base_urls = base_urls
+            .iter()
+            .map(|base_url| Url::parse(base_url).unwrap())
+            .collect::<Vec<_>>();
+        crate::HttpClient::builder(base_urls[0].clone())
+            .base_urls(base_urls)
+            .replica_strategy(strategy)
+            .tls_config(crate::TlsConfig {
+                insecure: Some(true),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_replica_failover() {
+        // nothing listens on 3999, the 503 of the flaky path moves on as well
+        let flaky_base = String::from("https://localhost:3000") + &flaky_path("replica") + "/";
+        let client = replicated_client(
+            &[
+                "https://localhost:3999/",
+                &flaky_base,
+                "https://127.0.0.1:3000/",
+            ],
+            crate::ReplicaStrategy::Failover,
+        );
+
+        let response = client.get("ok", None).await.unwrap();
+        assert_eq!(response.url().host_str(), Some("127.0.0.1"));
+        let health = client.replica_health();
+        assert!(!health[0].healthy);
+        assert_eq!(health[1].consecutive_failures, 1);
+        assert!(health[2].healthy);
+        assert_eq!(health[2].in_flight, 1);
+        drop(response);
+        assert_eq!(client.replica_health()[2].in_flight, 0);
+
+        // failed replicas are tried last until their cool-down is over
+        let response: crate::OkJson = client.get_json("ok", None).await.unwrap();
+        assert!(response.ok);
+        assert_eq!(client.replica_health()[0].consecutive_failures, 1);
+
+        // a post answered with a 503 may have been applied, it is not replayed
+        let flaky_base = String::from("https://localhost:3000") + &flaky_path("replica-post") + "/";
+        let client = replicated_client(
+            &[&flaky_base, "https://127.0.0.1:3000/"],
+            crate::ReplicaStrategy::Failover,
+        );
+        let response = client.post("ok", &(), None).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.url().host_str(), Some("localhost"));
+        assert_eq!(client.replica_health()[0].consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
+    async fn test_replica_balancing() {
+        let base_urls = ["https://localhost:3000/", "https://127.0.0.1:3000/"];
+
+        let client = replicated_client(&base_urls, crate::ReplicaStrategy::RoundRobin);
+        let mut hosts = Vec::new();
+        for _ in 0..4 {
+            let response = client.get("/", None).await.unwrap();
+            hosts.push(response.url().host_str().unwrap().to_string());
+        }
+        assert_eq!(hosts, ["localhost", "127.0.0.1", "localhost", "127.0.0.1"]);
+
+        let client = replicated_client(&base_urls, crate::ReplicaStrategy::LeastInFlight);
+        let first = client.get("/", None).await.unwrap();
+        let second = client.get("/", None).await.unwrap();
+        assert_ne!(first.url().host_str(), second.url().host_str());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tls-core")]
 
     async fn test_send_buffer() {
         let url_string = String::from("https://") + TEST_URL;
@@ -89,6 +1505,7 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: Some(true),
                 private_chain_bytes: None,
+                ..Default::default()
             }),
             None,
         );
@@ -107,7 +1524,7 @@ mod tests {
     }
 
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(feature = "tls-core")]
     #[cfg(feature = "async-fs")]
     async fn get_archive_to_dir() {
         let url_get_string =
@@ -120,6 +1537,7 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: Some(true),
                 private_chain_bytes: None,
+                ..Default::default()
             }),
             None,
         );
@@ -141,7 +1559,7 @@ mod tests {
     }
 
     #[tokio::test]
-    #[cfg(feature = "tls")]
+    #[cfg(feature = "tls-core")]
     #[cfg(feature = "async-fs")]
     async fn spawn_get_archive_to_dir() {
         let url_get_string =
@@ -154,24 +1572,32 @@ mod tests {
             Some(crate::TlsConfig {
                 insecure: Some(true),
                 private_chain_bytes: None,
+                ..Default::default()
             }),
             None,
         );
 
-        let cloned_client = client.clone();
-        tokio::spawn(async move {
-            let a = cloned_client
+        let cloned_client = client.with_timeouts(crate::Timeouts {
+            total: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        });
+        let response = tokio::spawn(async move {
+            cloned_client
                 .get_archive_to_dir(
                     url::Url::parse(&url_get_string).unwrap(),
                     &crate::ArchiveType::Gzip,
                     storage_path,
                     None,
                 )
-                .await;
+                .await
+        })
+        .await
+        .unwrap();
 
-            assert!(a.is_ok());
-        });
-
-        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        if response.is_err() {
+            println!("Error: {:?}", &response.err());
+        } else {
+            assert!(response.is_ok());
+        }
     }
 }
@@ -4,3 +4,20 @@ openssl req -x509 -newkey rsa:4096 -days 36500 -keyout ca_key.pem -out ca_cert.p
 openssl genpkey -algorithm RSA -out server_key.pem
 openssl req -new -key server_key.pem -out server_csr.pem -subj "/CN=localhost"
 openssl x509 -req -in server_csr.pem -CA ca_cert.pem -CAkey ca_key.pem -CAcreateserial -out server_cert.pem -days 36500 -extfile <(echo -e "subjectAltName=DNS:localhost\nkeyUsage=digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth")
+
+################### client certificate for mutual tls ###################
+
+openssl genpkey -algorithm RSA -out client_key.pem
+openssl req -new -key client_key.pem -out client_csr.pem -subj "/CN=client"
+openssl x509 -req -in client_csr.pem -CA ca_cert.pem -CAkey ca_key.pem -CAcreateserial -out client_cert.pem -days 36500 -extfile <(echo -e "keyUsage=digitalSignature\nextendedKeyUsage=clientAuth")
+openssl pkcs12 -export -in client_cert.pem -inkey client_key.pem -out client.p12 -passout pass:test
+
+################### certificate revocation lists ###################
+
+mkdir -p crl_db && touch crl_db/index.txt && echo 1000 > crl_db/crlnumber
+echo -e "[ca]\ndefault_ca = crl_ca\n[crl_ca]\ndatabase = crl_db/index.txt\ncrlnumber = crl_db/crlnumber\ndefault_md = sha256\ndefault_crl_days = 36500" > crl_db/ca.cnf
+openssl ca -config crl_db/ca.cnf -keyfile ca_key.pem -cert ca_cert.pem -gencrl -out ca_crl.pem
+openssl ca -config crl_db/ca.cnf -keyfile ca_key.pem -cert ca_cert.pem -revoke server_cert.pem
+openssl ca -config crl_db/ca.cnf -keyfile ca_key.pem -cert ca_cert.pem -gencrl -out server_revoked_crl.pem
+openssl crl -in server_revoked_crl.pem -outform DER -out server_revoked_crl.der
+rm -r crl_db
@@ -3,11 +3,56 @@ const https = require("https");
 
 const privateKey = fs.readFileSync("server_key.pem", "utf8");
 const certificate = fs.readFileSync("server_cert.pem", "utf8");
+const ca = fs.readFileSync("ca_cert.pem", "utf8");
 const credentials = { key: privateKey, cert: certificate };
 
+// request count and idempotency keys seen for each /flaky/ path
+const flakyCounts = {};
+const flakyKeys = {};
+
 const httpsServer = https.createServer(credentials, (req, res) => {
   const headers = req.headers;
   console.info("headers:", headers);
+  // never answers, for the timeout tests
+  if (req.url === "/stall") {
+    return;
+  }
+  // sends the head and half of the body, then nothing more
+  if (req.url === "/stall-body") {
+    res.writeHead(200, { "Content-Type": "application/json" });
+    res.write('{"ok":');
+    return;
+  }
+  // out of quota until the reset, for the rate limiter tests
+  if (req.url === "/ratelimited") {
+    res.writeHead(200, {
+      "Content-Type": "application/json",
+      "X-RateLimit-Remaining": "0",
+      "X-RateLimit-Reset": "1",
+    });
+    res.end(JSON.stringify({ ok: true }));
+    return;
+  }
+  // fails the first two requests to each /flaky/ path, for the retry tests
+  if (req.url.startsWith("/flaky/")) {
+    const url = new URL(req.url, "https://localhost");
+    flakyCounts[url.pathname] = (flakyCounts[url.pathname] || 0) + 1;
+    flakyKeys[url.pathname] = (flakyKeys[url.pathname] || []).concat(
+      req.headers["idempotency-key"] || []
+    );
+    if (flakyCounts[url.pathname] <= 2) {
+      const retryAfter = url.searchParams.get("retry_after");
+      res.writeHead(retryAfter ? 429 : 503, {
+        "Content-Type": "application/json",
+        ...(retryAfter ? { "Retry-After": retryAfter } : {}),
+      });
+      res.end(JSON.stringify({ error: "try again" }));
+      return;
+    }
+    res.writeHead(200, { "Content-Type": "application/json" });
+    res.end(JSON.stringify({ ok: true, idempotency_keys: flakyKeys[url.pathname] }));
+    return;
+  }
   res.writeHead(200, { "Content-Type": "application/json" });
   res.end(JSON.stringify({ ok: true }));
 });
@@ -15,3 +60,62 @@ const httpsServer = https.createServer(credentials, (req, res) => {
 httpsServer.listen(3000, () => {
   console.log("Server HTTPS in ascolto sulla porta 3000");
 });
+
+// mutual tls: only clients with a certificate signed by ca_cert.pem are accepted
+const mtlsServer = https.createServer(
+  { ...credentials, ca, requestCert: true, rejectUnauthorized: true },
+  (req, res) => {
+    const peer = req.socket.getPeerCertificate();
+    console.info("client certificate:", peer.subject);
+    res.writeHead(200, { "Content-Type": "application/json" });
+    res.end(JSON.stringify({ ok: true }));
+  }
+);
+
+mtlsServer.listen(3001, () => {
+  console.log("Server mTLS in ascolto sulla porta 3001");
+});
+
+// http proxy with basic auth, tunnels https through CONNECT
+const http = require("http");
+const net = require("net");
+
+const proxyAuth = "Basic " + Buffer.from("proxyuser:proxypass").toString("base64");
+const proxyServer = http.createServer((req, res) => {
+  res.writeHead(405);
+  res.end();
+});
+
+proxyServer.on("connect", (req, clientSocket, head) => {
+  if (req.headers["proxy-authorization"] !== proxyAuth) {
+    clientSocket.end("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+    return;
+  }
+  const [host, port] = req.url.split(":");
+  const serverSocket = net.connect(port, host, () => {
+    clientSocket.write("HTTP/1.1 200 Connection Established\r\n\r\n");
+    serverSocket.write(head);
+    serverSocket.pipe(clientSocket);
+    clientSocket.pipe(serverSocket);
+  });
+  serverSocket.on("error", () => clientSocket.end());
+  clientSocket.on("error", () => serverSocket.end());
+});
+
+proxyServer.listen(3002, () => {
+  console.log("Proxy HTTP in ascolto sulla porta 3002");
+});
+
+// plain http over a unix socket, answers ok only when the default header came along
+const socketPath = "/tmp/simplerusthttpsclient-test.sock";
+if (fs.existsSync(socketPath)) {
+  fs.unlinkSync(socketPath);
+}
+const unixServer = http.createServer((req, res) => {
+  res.writeHead(200, { "Content-Type": "application/json" });
+  res.end(JSON.stringify({ ok: req.headers["x-agent-token"] === "test" }));
+});
+
+unixServer.listen(socketPath, () => {
+  console.log("Server HTTP in ascolto su " + socketPath);
+});
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::time::Duration;
+
+use url::Url;
+
+/// Read-idle and total timeouts of a request, `None` waits forever.
+///
+/// Set the client defaults with [`HttpClientBuilder::timeouts`](crate::HttpClientBuilder::timeouts)
+/// and override them for some calls with [`HttpClient::with_timeouts`](crate::HttpClient::with_timeouts).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Longest wait for the response head or for the next chunk of the body.
+    /// The body of a `Response` handed back to the caller is only bound by `total`.
+    pub read: Option<Duration>,
+    /// Deadline for the whole request, from sending it to the end of the body.
+    /// Retries and replica failover happen within it.
+    pub total: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Values of `overrides` where set, the ones of `self` otherwise.
+    pub(crate) fn merge(self, overrides: Timeouts) -> Timeouts {
+        Timeouts {
+            read: overrides.read.or(self.read),
+            total: overrides.total.or(self.total),
+        }
+    }
+}
+
+/// Phase of a request that ran out of time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Opening the connection, TLS handshake included.
+    Connect,
+    /// Waiting for the response head or the next chunk of the body.
+    Read,
+    /// The whole request.
+    Total,
+}
+
+impl fmt::Display for TimeoutPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutPhase::Connect => "connect",
+            TimeoutPhase::Read => "read",
+            TimeoutPhase::Total => "total",
+        })
+    }
+}
+
+/// Request that timed out, carried by [`Error::Timeout`](crate::Error::Timeout).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeoutError {
+    pub phase: TimeoutPhase,
+    /// The timeout that elapsed, `None` when it was not set by this crate.
+    pub after: Option<Duration>,
+    pub url: Option<Url>,
+    /// Key of a `POST` or `PATCH` sent with an `Idempotency-Key`.
+    pub idempotency_key: Option<String>,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} timeout", self.phase)?;
+        if let Some(after) = self.after {
+            write!(f, " of {:?}", after)?;
+        }
+        match &self.url {
+            Some(url) => write!(f, " elapsed for {}", url),
+            None => f.write_str(" elapsed"),
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {}
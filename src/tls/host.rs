@@ -0,0 +1,106 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::ServerName;
+
+/// Host name a TLS setting applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Exactly this host name or IP address.
+    Exact(String),
+    /// Any subdomain of this domain, written `*.example.com`.
+    Wildcard(String),
+    /// Any IP address in this network, written `10.0.0.0/8`.
+    Cidr { network: IpAddr, prefix_len: u8 },
+}
+
+impl HostPattern {
+    pub fn matches_host(&self, host: &str) -> bool {
+        let host = host
+            .trim_matches(['[', ']'])
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        match self {
+            HostPattern::Exact(name) => host == *name,
+            HostPattern::Wildcard(domain) => host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+            HostPattern::Cidr {
+                network,
+                prefix_len,
+            } => host
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| in_network(ip, *network, *prefix_len)),
+        }
+    }
+
+    #[cfg(not(feature = "native-tls"))]
+    pub(crate) fn matches(&self, server_name: &ServerName<'_>) -> bool {
+        self.matches_host(&server_name.to_str())
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn parse_cidr(pattern: &str) -> Option<HostPattern> {
+    let (network, prefix_len) = pattern.split_once('/')?;
+    let network = network.trim_matches(['[', ']']).parse::<IpAddr>().ok()?;
+    let prefix_len = prefix_len.parse::<u8>().ok()?;
+    let max_len = if network.is_ipv4() { 32 } else { 128 };
+    (prefix_len <= max_len).then_some(HostPattern::Cidr {
+        network,
+        prefix_len,
+    })
+}
+
+impl FromStr for HostPattern {
+    type Err = Infallible;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Ok(pattern.into())
+    }
+}
+
+impl From<&str> for HostPattern {
+    fn from(pattern: &str) -> Self {
+        if let Some(cidr) = parse_cidr(pattern) {
+            return cidr;
+        }
+        let pattern = pattern
+            .trim_matches(['[', ']'])
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(domain) => HostPattern::Wildcard(domain.to_string()),
+            None => HostPattern::Exact(pattern),
+        }
+    }
+}
+
+impl fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostPattern::Exact(name) => f.write_str(name),
+            HostPattern::Wildcard(domain) => write!(f, "*.{}", domain),
+            HostPattern::Cidr {
+                network,
+                prefix_len,
+            } => write!(f, "{}/{}", network, prefix_len),
+        }
+    }
+}
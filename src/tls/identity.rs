@@ -0,0 +1,71 @@
+use std::fmt;
+
+#[cfg(not(feature = "native-tls"))]
+use p12_keystore::{KeyStore, Pkcs12ImportPolicy};
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+/// Client certificate chain and private key used for mutual TLS.
+#[derive(Clone)]
+pub enum ClientIdentity {
+    /// PEM certificate chain (leaf first) and a PEM private key in PKCS#8, PKCS#1 or SEC1 form,
+    /// the native-tls backend only reads PKCS#8.
+    Pem {
+        cert_chain: Vec<u8>,
+        private_key: Vec<u8>,
+    },
+    /// DER encoded PKCS#12 archive holding the certificate chain and private key.
+    Pkcs12 { der: Vec<u8>, password: String },
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ClientIdentity {
+    pub(crate) fn load(
+        &self,
+    ) -> anyhow::Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+        match self {
+            ClientIdentity::Pem {
+                cert_chain,
+                private_key,
+            } => {
+                let cert_chain = rustls_pemfile::certs(&mut cert_chain.as_slice())
+                    .collect::<Result<Vec<_>, _>>()?;
+                if cert_chain.is_empty() {
+                    anyhow::bail!("no certificate found in client identity");
+                }
+                let private_key = rustls_pemfile::private_key(&mut private_key.as_slice())?
+                    .ok_or_else(|| anyhow::anyhow!("no private key found in client identity"))?;
+                Ok((cert_chain, private_key))
+            }
+            ClientIdentity::Pkcs12 { der, password } => {
+                let keystore = KeyStore::from_pkcs12(der, password, Pkcs12ImportPolicy::Strict)?;
+                let (_, key_chain) = keystore
+                    .private_key_chain()
+                    .ok_or_else(|| anyhow::anyhow!("no private key found in PKCS#12 archive"))?;
+                let cert_chain = key_chain
+                    .certs()
+                    .iter()
+                    .map(|cert| CertificateDer::from(cert.as_der().to_vec()))
+                    .collect::<Vec<_>>();
+                if cert_chain.is_empty() {
+                    anyhow::bail!("no certificate found in PKCS#12 archive");
+                }
+                let private_key =
+                    PrivatePkcs8KeyDer::from(key_chain.key().as_der().to_vec()).into();
+                Ok((cert_chain, private_key))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientIdentity::Pem { cert_chain, .. } => f
+                .debug_struct("Pem")
+                .field("cert_chain", cert_chain)
+                .finish_non_exhaustive(),
+            ClientIdentity::Pkcs12 { .. } => f.debug_struct("Pkcs12").finish_non_exhaustive(),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::{Response, Version};
+use url::Url;
+use x509_parser::extensions::GeneralName;
+
+use super::TlsVersion;
+use crate::{TimeoutError, TimeoutPhase};
+
+/// The parts of an X.509 certificate a monitoring job usually reports.
+#[derive(Clone, Debug)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub subject_alt_names: Vec<String>,
+    pub serial: String,
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+    pub der: Vec<u8>,
+}
+
+impl CertificateInfo {
+    pub fn from_der(der: &[u8]) -> anyhow::Result<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)?;
+        let subject_alt_names = match cert.subject_alternative_name()? {
+            Some(san) => san
+                .value
+                .general_names
+                .iter()
+                .map(|name| match name {
+                    GeneralName::DNSName(dns_name) => dns_name.to_string(),
+                    GeneralName::IPAddress(ip) => ip_to_string(ip),
+                    other => other.to_string(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            subject_alt_names,
+            serial: cert.raw_serial_as_string(),
+            not_before: to_system_time(cert.validity().not_before.timestamp()),
+            not_after: to_system_time(cert.validity().not_after.timestamp()),
+            der: der.to_vec(),
+        })
+    }
+
+    /// Time left before the certificate expires, `None` when it already has.
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.not_after.duration_since(SystemTime::now()).ok()
+    }
+}
+
+fn to_system_time(timestamp: i64) -> SystemTime {
+    if timestamp >= 0 {
+        UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+    }
+}
+
+fn ip_to_string(ip: &[u8]) -> String {
+    match ip.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(ip).unwrap()).to_string(),
+        16 => IpAddr::from(<[u8; 16]>::try_from(ip).unwrap()).to_string(),
+        _ => format!("{:?}", ip),
+    }
+}
+
+/// Outcome of a handshake made by [`crate::HttpClient::inspect_tls`].
+#[derive(Clone, Debug)]
+pub struct TlsReport {
+    pub protocol_version: Option<TlsVersion>,
+    pub cipher_suite: Option<String>,
+    pub alpn_protocol: Option<String>,
+    pub peer_certificate: CertificateInfo,
+    /// Intermediates sent by the server after the leaf, in the order it sent them.
+    pub intermediates: Vec<CertificateInfo>,
+}
+
+impl TlsReport {
+    /// Time left before the first certificate of the chain expires.
+    pub fn expires_in(&self) -> Option<Duration> {
+        std::iter::once(&self.peer_certificate)
+            .chain(&self.intermediates)
+            .map(CertificateInfo::expires_in)
+            .min()
+            .flatten()
+    }
+}
+
+/// TLS details reqwest keeps on a response.
+///
+/// reqwest only surfaces the peer certificate of the connection. The negotiated
+/// protocol version and cipher suite are not recorded per response, they are
+/// available through [`crate::HttpClient::inspect_tls`] on a new handshake.
+#[derive(Clone, Debug)]
+pub struct ResponseTlsDetails {
+    /// HTTP version of the response, `HTTP/2` is only used when `h2` was negotiated through ALPN.
+    pub http_version: Version,
+    /// Derived from `http_version`: `h2` for HTTP/2, `None` otherwise since
+    /// reqwest does not tell whether `http/1.1` was negotiated or ALPN was not used.
+    pub alpn_protocol: Option<String>,
+    pub peer_certificate: Option<CertificateInfo>,
+}
+
+pub trait ResponseTlsExt {
+    /// `None` when the response did not come over TLS.
+    fn tls_details(&self) -> Option<ResponseTlsDetails>;
+}
+
+impl ResponseTlsExt for Response {
+    fn tls_details(&self) -> Option<ResponseTlsDetails> {
+        let tls_info = self.extensions().get::<reqwest::tls::TlsInfo>()?;
+        Some(ResponseTlsDetails {
+            http_version: self.version(),
+            alpn_protocol: (self.version() == Version::HTTP_2).then(|| String::from("h2")),
+            peer_certificate: tls_info
+                .peer_certificate()
+                .and_then(|der| CertificateInfo::from_der(der).ok()),
+        })
+    }
+}
+
+/// Timeouts of [`crate::HttpClient::inspect_tls`], the caller applies the total one.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct InspectTimeouts {
+    /// For the TCP connection.
+    pub(crate) connect: Option<Duration>,
+    /// For the handshake, the server has to answer within it.
+    pub(crate) read: Option<Duration>,
+}
+
+impl InspectTimeouts {
+    pub(super) async fn connect<F: Future>(
+        &self,
+        url: &Url,
+        future: F,
+    ) -> anyhow::Result<F::Output> {
+        within(self.connect, TimeoutPhase::Connect, url, future).await
+    }
+
+    pub(super) async fn handshake<F: Future>(
+        &self,
+        url: &Url,
+        future: F,
+    ) -> anyhow::Result<F::Output> {
+        within(self.read, TimeoutPhase::Read, url, future).await
+    }
+}
+
+async fn within<F: Future>(
+    timeout: Option<Duration>,
+    phase: TimeoutPhase,
+    url: &Url,
+    future: F,
+) -> anyhow::Result<F::Output> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.map_err(|_| {
+            TimeoutError {
+                phase,
+                after: Some(timeout),
+                url: Some(url.clone()),
+                idempotency_key: None,
+            }
+            .into()
+        }),
+        None => Ok(future.await),
+    }
+}
+
+/// Host and port [`crate::HttpClient::inspect_tls`] connects to.
+pub(super) fn target(url: &Url) -> anyhow::Result<(&str, u16)> {
+    if url.scheme() != "https" {
+        anyhow::bail!("{} is not an https url", url);
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("{} has no host", url))?
+        .trim_matches(['[', ']']);
+    Ok((host, url.port_or_known_default().unwrap_or(443)))
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+#[cfg(not(feature = "native-tls"))]
+use std::fs::{File, OpenOptions};
+#[cfg(not(feature = "native-tls"))]
+use std::io::Write;
+#[cfg(not(feature = "native-tls"))]
+use std::sync::{Arc, Mutex};
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::KeyLog;
+
+#[cfg(not(feature = "native-tls"))]
+use super::hex;
+
+/// Where TLS session secrets are written, in NSS key log format.
+///
+/// This is a debugging aid: anyone who can read the file can decrypt the
+/// captured traffic of every connection made while it was enabled.
+#[derive(Clone, Debug)]
+pub enum KeyLogDestination {
+    /// Appends to this file.
+    File(PathBuf),
+    /// Appends to the file named by the `SSLKEYLOGFILE` environment variable, if set.
+    Env,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl KeyLogDestination {
+    pub(crate) fn key_log(&self) -> anyhow::Result<Arc<dyn KeyLog>> {
+        match self {
+            KeyLogDestination::File(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                Ok(Arc::new(FileKeyLog {
+                    file: Mutex::new(file),
+                }))
+            }
+            KeyLogDestination::Env => Ok(Arc::new(rustls::KeyLogFile::new())),
+        }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+struct FileKeyLog {
+    file: Mutex<File>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl KeyLog for FileKeyLog {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format!("{} {} {}\n", label, hex(client_random), hex(secret));
+        // losing a line only makes that connection harder to decrypt
+        let _ = self.file.lock().unwrap().write_all(line.as_bytes());
+    }
+}
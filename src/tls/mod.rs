@@ -0,0 +1,143 @@
+mod host;
+mod identity;
+mod inspect;
+mod keylog;
+#[cfg(feature = "native-tls")]
+mod native_backend;
+mod pins;
+mod protocol;
+mod reload;
+mod revocation;
+mod roots;
+#[cfg(not(feature = "native-tls"))]
+mod rustls_backend;
+mod verifier;
+
+use std::error::Error as StdError;
+use std::sync::Arc;
+
+use rustls::CertificateError;
+
+use crate::{BoxError, Error, TimeoutError};
+
+pub use host::HostPattern;
+pub use identity::ClientIdentity;
+pub(crate) use inspect::InspectTimeouts;
+pub use inspect::{CertificateInfo, ResponseTlsDetails, ResponseTlsExt, TlsReport};
+pub use keylog::KeyLogDestination;
+#[cfg(feature = "native-tls")]
+pub(crate) use native_backend::{inspect, use_connector, TlsConnector};
+pub use pins::{CertificatePin, PinHash, PinMismatchError};
+pub use protocol::TlsVersion;
+pub use reload::{FileTlsSource, TlsMaterial, TlsMaterialSource};
+pub use revocation::{CrlSource, RevocationCheck};
+pub use roots::{CaSource, SystemRoots};
+#[cfg(not(feature = "native-tls"))]
+pub(crate) use rustls_backend::{inspect, use_connector, TlsConnector};
+pub use verifier::{
+    CertificateRejectedError, CertificateVerdict, CertificateVerifier, ServerCertificate,
+};
+
+/// TLS settings, applied the same way by every backend.
+///
+/// The native-tls backend has no hooks into certificate verification or the
+/// session secrets, building a client fails when an option it cannot honour
+/// is set.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    pub insecure: Option<bool>,
+    pub private_chain_bytes: Option<Vec<u8>>,
+    /// Certificate and key presented to servers that ask for a client certificate (mutual TLS).
+    pub client_identity: Option<ClientIdentity>,
+    /// Extra trusted CAs, added to `private_chain_bytes`.
+    pub ca_sources: Option<Vec<CaSource>>,
+    /// Trust only `private_chain_bytes` and `ca_sources`, neither the built-in web roots nor `system_roots`.
+    pub private_roots_only: Option<bool>,
+    /// Also trust the platform trust store, the native-tls backend always does.
+    pub system_roots: Option<SystemRoots>,
+    /// Hosts `insecure` and the private CAs apply to, every other host is validated
+    /// against the built-in web roots and `system_roots` only. `None` applies them to every host.
+    pub trusted_hosts: Option<Vec<HostPattern>>,
+    /// CRLs of the private CAs, certificates of other CAs have an unknown status.
+    pub revocation: Option<RevocationCheck>,
+    /// Leaf certificate pins, checked on every handshake after the chain is validated.
+    pub pins: Option<Vec<CertificatePin>>,
+    /// Custom verifier that accepts or rejects the server certificate, pins still apply on top.
+    pub verifier: Option<Arc<dyn CertificateVerifier>>,
+    pub min_version: Option<TlsVersion>,
+    pub max_version: Option<TlsVersion>,
+    /// Allowed cipher suites by IANA name, e.g. `TLS13_AES_256_GCM_SHA384`.
+    pub cipher_suites: Option<Vec<String>>,
+    /// ALPN protocols offered in the handshake, `h2` and `http/1.1` are supported.
+    pub alpn_protocols: Option<Vec<String>>,
+    /// Source of client identity and CAs that rotate, polled before every new handshake.
+    pub material_source: Option<Arc<dyn TlsMaterialSource>>,
+    /// Debugging only, writes the session secrets so captured traffic can be decrypted.
+    pub key_log: Option<KeyLogDestination>,
+    /// Settings for connections to specific hosts, used as is in place of this config.
+    /// The first matching pattern wins, nothing is inherited from the outer config.
+    pub host_configs: Option<Vec<(HostPattern, TlsConfig)>>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Errors in the chain of `err`, looking into the io errors the TLS streams wrap.
+fn chain<'a>(
+    err: &'a (dyn StdError + 'static),
+) -> impl Iterator<Item = &'a (dyn StdError + 'static)> {
+    std::iter::successors(Some(err), |&current| {
+        let inner = current
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io_err| io_err.get_ref())
+            .map(|inner| inner as &(dyn StdError + 'static));
+        inner.or_else(|| current.source())
+    })
+}
+
+/// Digs the typed error out of a failed handshake, so callers can match it
+/// instead of matching on the reqwest error message.
+pub(crate) fn handshake_error(err: &(dyn StdError + 'static)) -> Option<BoxError> {
+    chain(err).find_map(|current| {
+        let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) =
+            current.downcast_ref::<rustls::Error>()
+        else {
+            return None;
+        };
+        if let Some(pin_err) = other.0.downcast_ref::<PinMismatchError>() {
+            return Some(Box::new(pin_err.clone()) as BoxError);
+        }
+        other
+            .0
+            .downcast_ref::<CertificateRejectedError>()
+            .map(|rejected_err| Box::new(rejected_err.clone()) as BoxError)
+    })
+}
+
+/// Whether `err` comes from the TLS library, a failed handshake most of the time.
+pub(crate) fn is_tls_failure(err: &(dyn StdError + 'static)) -> bool {
+    chain(err).any(|current| {
+        #[cfg(feature = "native-tls")]
+        if current.is::<native_tls::Error>() {
+            return true;
+        }
+        current.is::<rustls::Error>()
+    })
+}
+
+/// Error of [`inspect`], the typed handshake errors are kept as is.
+pub(crate) fn inspect_error(err: anyhow::Error) -> Error {
+    let err = match err.downcast::<TimeoutError>() {
+        Ok(timeout_err) => return timeout_err.into(),
+        Err(err) => err,
+    };
+    if let Some(handshake_err) = handshake_error(err.as_ref()) {
+        return Error::tls(handshake_err);
+    }
+    match err.downcast::<std::io::Error>() {
+        Ok(io_err) if !is_tls_failure(&io_err) => Error::Io(io_err),
+        Ok(io_err) => Error::tls(io_err),
+        Err(err) => Error::tls(err),
+    }
+}
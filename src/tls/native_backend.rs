@@ -0,0 +1,148 @@
+use native_tls::{Certificate, Identity, Protocol};
+use reqwest::ClientBuilder;
+use url::Url;
+
+use super::inspect::InspectTimeouts;
+use super::protocol;
+use super::{CaSource, CertificateInfo, ClientIdentity, TlsConfig, TlsReport, TlsVersion};
+
+/// native-tls connector shared by every connection of a reqwest client.
+pub(crate) type TlsConnector = native_tls::TlsConnector;
+
+pub(crate) fn use_connector(builder: ClientBuilder, connector: &TlsConnector) -> ClientBuilder {
+    builder.use_preconfigured_tls(connector.clone())
+}
+
+impl TlsConfig {
+    pub(crate) fn connector(&self) -> anyhow::Result<TlsConnector> {
+        self.check_native_support()?;
+        let mut builder = TlsConnector::builder();
+        builder.danger_accept_invalid_certs(self.insecure == Some(true));
+
+        // the built-in roots are the ones of the platform, not the bundled web roots
+        let private_roots_only = self.private_roots_only == Some(true);
+        builder.disable_built_in_roots(private_roots_only);
+        let private_chain = self
+            .private_chain_bytes
+            .as_ref()
+            .map(|bytes| CaSource::Pem(bytes.clone()));
+        let mut private_roots = 0;
+        for ca_source in private_chain.iter().chain(self.ca_sources.iter().flatten()) {
+            for cert in root_certificates(ca_source)? {
+                builder.add_root_certificate(cert);
+                private_roots += 1;
+            }
+        }
+        if private_roots_only && private_roots == 0 {
+            anyhow::bail!("private_roots_only is set but no private CA was given");
+        }
+
+        if let Some(identity) = &self.client_identity {
+            builder.identity(native_identity(identity)?);
+        }
+
+        let (min_version, max_version) =
+            protocol::version_range(self.min_version, self.max_version)?;
+        builder.min_protocol_version(Some(native_protocol(min_version)));
+        builder.max_protocol_version(Some(native_protocol(max_version)));
+
+        if let Some(alpn_protocols) = &self.alpn_protocols {
+            protocol::alpn_protocols(alpn_protocols)?;
+            let alpn_protocols = alpn_protocols
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            builder.request_alpns(&alpn_protocols);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// The options that need hooks native-tls does not have.
+    fn check_native_support(&self) -> anyhow::Result<()> {
+        let unsupported = [
+            ("trusted_hosts", self.trusted_hosts.is_some()),
+            ("revocation", self.revocation.is_some()),
+            ("pins", self.pins.is_some()),
+            ("verifier", self.verifier.is_some()),
+            ("cipher_suites", self.cipher_suites.is_some()),
+            ("material_source", self.material_source.is_some()),
+            ("key_log", self.key_log.is_some()),
+        ];
+        match unsupported.iter().find(|(_, set)| *set) {
+            Some((option, _)) => {
+                anyhow::bail!("{} is not supported by the native-tls backend", option)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn native_protocol(version: TlsVersion) -> Protocol {
+    match version {
+        TlsVersion::Tls12 => Protocol::Tlsv12,
+        TlsVersion::Tls13 => Protocol::Tlsv13,
+    }
+}
+
+fn root_certificates(ca_source: &CaSource) -> anyhow::Result<Vec<Certificate>> {
+    let certs = ca_source.certificates()?;
+    if let CaSource::Dir(path) = ca_source {
+        // same as the rustls backend, unrelated files in a certificate directory are skipped
+        let certs = certs
+            .iter()
+            .filter_map(|cert| Certificate::from_der(cert).ok())
+            .collect::<Vec<_>>();
+        if certs.is_empty() {
+            anyhow::bail!("no certificate found in {}", path.display());
+        }
+        return Ok(certs);
+    }
+    certs
+        .iter()
+        .map(|cert| Ok(Certificate::from_der(cert)?))
+        .collect()
+}
+
+fn native_identity(identity: &ClientIdentity) -> anyhow::Result<Identity> {
+    match identity {
+        ClientIdentity::Pem {
+            cert_chain,
+            private_key,
+        } => Ok(Identity::from_pkcs8(cert_chain, private_key)?),
+        ClientIdentity::Pkcs12 { der, password } => Ok(Identity::from_pkcs12(der, password)?),
+    }
+}
+
+pub(crate) async fn inspect(
+    connector: TlsConnector,
+    url: &Url,
+    timeouts: InspectTimeouts,
+) -> anyhow::Result<TlsReport> {
+    let (host, port) = super::inspect::target(url)?;
+
+    let tcp_stream = timeouts
+        .connect(url, tokio::net::TcpStream::connect((host, port)))
+        .await??;
+    let tls_stream = timeouts
+        .handshake(
+            url,
+            tokio_native_tls::TlsConnector::from(connector).connect(host, tcp_stream),
+        )
+        .await??;
+    let connection = tls_stream.get_ref();
+
+    let peer_certificate = connection
+        .peer_certificate()?
+        .ok_or_else(|| anyhow::anyhow!("{} sent no certificate", host))?;
+
+    // native-tls reports neither the protocol version, the cipher suite nor the chain
+    Ok(TlsReport {
+        protocol_version: None,
+        cipher_suite: None,
+        alpn_protocol: connection
+            .negotiated_alpn()?
+            .map(|protocol| String::from_utf8_lossy(&protocol).into_owned()),
+        peer_certificate: CertificateInfo::from_der(&peer_certificate.to_der()?)?,
+        intermediates: Vec::new(),
+    })
+}
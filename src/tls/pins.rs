@@ -0,0 +1,161 @@
+use std::fmt;
+#[cfg(not(feature = "native-tls"))]
+use std::sync::Arc;
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+#[cfg(not(feature = "native-tls"))]
+use rustls::{CertificateError, DigitallySignedStruct, OtherError, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use super::{hex, HostPattern};
+
+/// SHA-256 pins enforced for one host on top of the normal chain validation.
+#[derive(Clone, Debug)]
+pub struct CertificatePin {
+    pub host: HostPattern,
+    /// The handshake is accepted when any of these matches the leaf certificate.
+    pub hashes: Vec<PinHash>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinHash {
+    /// SHA-256 of the leaf certificate's DER SubjectPublicKeyInfo.
+    Spki([u8; 32]),
+    /// SHA-256 of the whole DER leaf certificate.
+    Certificate([u8; 32]),
+}
+
+impl PinHash {
+    pub fn spki_of(cert_der: &[u8]) -> anyhow::Result<Self> {
+        Ok(PinHash::Spki(spki_sha256(cert_der)?))
+    }
+
+    pub fn certificate_of(cert_der: &[u8]) -> Self {
+        PinHash::Certificate(Sha256::digest(cert_der).into())
+    }
+}
+
+/// The server certificate of `host` matched none of its pins.
+#[derive(Clone, Debug)]
+pub struct PinMismatchError {
+    pub host: String,
+    pub seen_spki: [u8; 32],
+    pub seen_certificate: [u8; 32],
+}
+
+impl fmt::Display for PinMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "certificate pin mismatch for {}: seen spki sha256 {}, certificate sha256 {}",
+            self.host,
+            hex(&self.seen_spki),
+            hex(&self.seen_certificate)
+        )
+    }
+}
+
+impl std::error::Error for PinMismatchError {}
+
+fn spki_sha256(cert_der: &[u8]) -> anyhow::Result<[u8; 32]> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert_der)?;
+    Ok(Sha256::digest(cert.public_key().raw).into())
+}
+
+/// Runs the wrapped verifier, then checks the leaf against the pins of the host.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+pub(crate) struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<CertificatePin>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl PinningVerifier {
+    pub(crate) fn new(inner: Arc<dyn ServerCertVerifier>, pins: Vec<CertificatePin>) -> Self {
+        Self { inner, pins }
+    }
+
+    fn check_pins(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        server_name: &ServerName<'_>,
+    ) -> Result<(), rustls::Error> {
+        let mut host_pins = self
+            .pins
+            .iter()
+            .filter(|pin| pin.host.matches(server_name))
+            .flat_map(|pin| pin.hashes.iter())
+            .peekable();
+        if host_pins.peek().is_none() {
+            return Ok(());
+        }
+
+        let seen_spki = spki_sha256(end_entity)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        let seen_certificate: [u8; 32] = Sha256::digest(end_entity).into();
+
+        let pinned = host_pins.any(|pin| match pin {
+            PinHash::Spki(hash) => *hash == seen_spki,
+            PinHash::Certificate(hash) => *hash == seen_certificate,
+        });
+        if pinned {
+            Ok(())
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(PinMismatchError {
+                    host: server_name.to_str().into_owned(),
+                    seen_spki,
+                    seen_certificate,
+                })),
+            )))
+        }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        self.check_pins(end_entity, server_name)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
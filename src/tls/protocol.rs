@@ -0,0 +1,83 @@
+#[cfg(not(feature = "native-tls"))]
+use rustls::crypto::CryptoProvider;
+#[cfg(not(feature = "native-tls"))]
+use rustls::SupportedProtocolVersion;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl TlsVersion {
+    fn supported(&self) -> &'static SupportedProtocolVersion {
+        match self {
+            TlsVersion::Tls12 => &rustls::version::TLS12,
+            TlsVersion::Tls13 => &rustls::version::TLS13,
+        }
+    }
+}
+
+/// Fills in the defaults, TLS 1.2 to TLS 1.3.
+pub(crate) fn version_range(
+    min_version: Option<TlsVersion>,
+    max_version: Option<TlsVersion>,
+) -> anyhow::Result<(TlsVersion, TlsVersion)> {
+    let min_version = min_version.unwrap_or(TlsVersion::Tls12);
+    let max_version = max_version.unwrap_or(TlsVersion::Tls13);
+    if min_version > max_version {
+        anyhow::bail!(
+            "min_version {:?} is above max_version {:?}",
+            min_version,
+            max_version
+        );
+    }
+    Ok((min_version, max_version))
+}
+
+#[cfg(not(feature = "native-tls"))]
+pub(crate) fn protocol_versions(
+    min_version: Option<TlsVersion>,
+    max_version: Option<TlsVersion>,
+) -> anyhow::Result<Vec<&'static SupportedProtocolVersion>> {
+    let (min_version, max_version) = version_range(min_version, max_version)?;
+    Ok([TlsVersion::Tls12, TlsVersion::Tls13]
+        .iter()
+        .filter(|version| (min_version..=max_version).contains(version))
+        .map(TlsVersion::supported)
+        .collect())
+}
+
+/// Keeps only the named cipher suites, in the order the provider prefers them.
+#[cfg(not(feature = "native-tls"))]
+pub(crate) fn restrict_cipher_suites(
+    provider: &mut CryptoProvider,
+    names: &[String],
+) -> anyhow::Result<()> {
+    for name in names {
+        let known = provider
+            .cipher_suites
+            .iter()
+            .any(|suite| suite.suite().as_str() == Some(name.as_str()));
+        if !known {
+            anyhow::bail!("unknown cipher suite {}", name);
+        }
+    }
+    provider.cipher_suites.retain(|suite| {
+        names
+            .iter()
+            .any(|name| suite.suite().as_str() == Some(name))
+    });
+    Ok(())
+}
+
+pub(crate) fn alpn_protocols(protocols: &[String]) -> anyhow::Result<Vec<Vec<u8>>> {
+    protocols
+        .iter()
+        .map(|protocol| match protocol.as_str() {
+            "h2" | "http/1.1" => Ok(protocol.as_bytes().to_vec()),
+            _ => Err(anyhow::anyhow!("unsupported ALPN protocol {}", protocol)),
+        })
+        .collect()
+}
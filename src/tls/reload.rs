@@ -0,0 +1,319 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+#[cfg(not(feature = "native-tls"))]
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(not(feature = "native-tls"))]
+use rustls::client::{ClientSessionMemoryCache, ClientSessionStore, ResolvesClientCert};
+#[cfg(not(feature = "native-tls"))]
+use rustls::client::{Tls12ClientSessionValue, Tls13ClientSessionValue};
+#[cfg(not(feature = "native-tls"))]
+use rustls::crypto::CryptoProvider;
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+#[cfg(not(feature = "native-tls"))]
+use rustls::sign::CertifiedKey;
+#[cfg(not(feature = "native-tls"))]
+use rustls::{DigitallySignedStruct, NamedGroup, SignatureScheme};
+
+#[cfg(not(feature = "native-tls"))]
+use super::TlsConfig;
+use super::{CaSource, ClientIdentity};
+
+/// Number of TLS sessions kept for resumption, same as the rustls default.
+#[cfg(not(feature = "native-tls"))]
+const SESSION_CACHE_SIZE: usize = 256;
+
+/// Supplies TLS material that may change while the client is running.
+///
+/// Polled before every new handshake, connections that are already open keep
+/// the material they were made with.
+pub trait TlsMaterialSource: fmt::Debug + Send + Sync {
+    /// Returns the material to use from now on, or `None` when nothing changed.
+    fn reload(&self) -> anyhow::Result<Option<TlsMaterial>>;
+
+    /// Called when `reload` failed or its material could not be loaded, the
+    /// client keeps the current material. The source should return the new
+    /// material again on the next poll, a half-written file may be fine by then.
+    fn reload_failed(&self, _err: &anyhow::Error) {}
+}
+
+/// Replaces the matching [`TlsConfig`](super::TlsConfig) fields, `None` keeps the current value.
+#[derive(Clone, Debug, Default)]
+pub struct TlsMaterial {
+    pub client_identity: Option<ClientIdentity>,
+    pub ca_sources: Option<Vec<CaSource>>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl TlsMaterial {
+    fn apply_to(self, tls_config: &mut TlsConfig) {
+        if self.client_identity.is_some() {
+            tls_config.client_identity = self.client_identity;
+        }
+        if self.ca_sources.is_some() {
+            tls_config.ca_sources = self.ca_sources;
+        }
+    }
+}
+
+/// Reloads PEM files when their modification time changes.
+#[derive(Debug, Default)]
+pub struct FileTlsSource {
+    identity: Option<(PathBuf, PathBuf)>,
+    ca_files: Vec<PathBuf>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl FileTlsSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_identity(mut self, cert_chain: PathBuf, private_key: PathBuf) -> Self {
+        self.identity = Some((cert_chain, private_key));
+        self
+    }
+
+    pub fn ca_file(mut self, path: PathBuf) -> Self {
+        self.ca_files.push(path);
+        self
+    }
+
+    /// Why the files could not be loaded, `None` once the client uses their current content.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.identity
+            .iter()
+            .flat_map(|(cert_chain, private_key)| [cert_chain, private_key])
+            .chain(&self.ca_files)
+    }
+}
+
+impl TlsMaterialSource for FileTlsSource {
+    fn reload(&self) -> anyhow::Result<Option<TlsMaterial>> {
+        let modified = self
+            .paths()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        let mut last_modified = self.modified.lock().unwrap();
+        if *last_modified == modified {
+            // a failed load forgets the times, so this material is in use
+            *self.last_error.lock().unwrap() = None;
+            return Ok(None);
+        }
+
+        let client_identity = match &self.identity {
+            Some((cert_chain, private_key)) => Some(ClientIdentity::Pem {
+                cert_chain: std::fs::read(cert_chain)?,
+                private_key: std::fs::read(private_key)?,
+            }),
+            None => None,
+        };
+        let ca_sources = (!self.ca_files.is_empty())
+            .then(|| self.ca_files.iter().cloned().map(CaSource::File).collect());
+        *last_modified = modified;
+        Ok(Some(TlsMaterial {
+            client_identity,
+            ca_sources,
+        }))
+    }
+
+    fn reload_failed(&self, err: &anyhow::Error) {
+        self.modified.lock().unwrap().clear();
+        *self.last_error.lock().unwrap() = Some(format!("{:#}", err));
+    }
+}
+
+/// Everything built from the current material, swapped as a whole on reload.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+struct Loaded {
+    tls_config: TlsConfig,
+    verifier: Arc<dyn ServerCertVerifier>,
+    identity: Option<Arc<CertifiedKey>>,
+    sessions: Arc<ClientSessionMemoryCache>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl Loaded {
+    fn new(tls_config: TlsConfig, provider: &Arc<CryptoProvider>) -> anyhow::Result<Self> {
+        Ok(Self {
+            verifier: tls_config.server_verifier(provider)?,
+            identity: tls_config.certified_key(provider)?.map(Arc::new),
+            sessions: Arc::new(ClientSessionMemoryCache::new(SESSION_CACHE_SIZE)),
+            tls_config,
+        })
+    }
+}
+
+/// Server verifier, client certificate resolver and session store of a
+/// `ClientConfig` whose material comes from a [`TlsMaterialSource`].
+///
+/// rustls asks the session store and the certificate resolver at the start of
+/// every handshake, which is where the source is polled. Cached sessions are
+/// dropped on reload so that no connection resumes with the old material.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+pub(crate) struct ReloadingTls {
+    provider: Arc<CryptoProvider>,
+    source: Arc<dyn TlsMaterialSource>,
+    loaded: RwLock<Arc<Loaded>>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ReloadingTls {
+    pub(crate) fn new(
+        mut tls_config: TlsConfig,
+        provider: Arc<CryptoProvider>,
+        source: Arc<dyn TlsMaterialSource>,
+    ) -> anyhow::Result<Self> {
+        if let Some(material) = source.reload()? {
+            material.apply_to(&mut tls_config);
+        }
+        let loaded = Loaded::new(tls_config, &provider)?;
+        Ok(Self {
+            provider,
+            source,
+            loaded: RwLock::new(Arc::new(loaded)),
+        })
+    }
+
+    /// Picks up new material, keeping the current one and telling the source
+    /// when it cannot be loaded.
+    fn refresh(&self) -> Arc<Loaded> {
+        let loaded = self.source.reload().and_then(|material| {
+            let Some(material) = material else {
+                return Ok(None);
+            };
+            let mut tls_config = self.current().tls_config.clone();
+            material.apply_to(&mut tls_config);
+            Loaded::new(tls_config, &self.provider).map(Some)
+        });
+        match loaded {
+            Ok(Some(loaded)) => *self.loaded.write().unwrap() = Arc::new(loaded),
+            Ok(None) => {}
+            Err(err) => self.source.reload_failed(&err),
+        }
+        self.current()
+    }
+
+    fn current(&self) -> Arc<Loaded> {
+        self.loaded.read().unwrap().clone()
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ServerCertVerifier for ReloadingTls {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.current().verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.current()
+            .verifier
+            .verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.current()
+            .verifier
+            .verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.current().verifier.supported_verify_schemes()
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ResolvesClientCert for ReloadingTls {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        self.current().identity.clone()
+    }
+
+    fn has_certs(&self) -> bool {
+        self.refresh().identity.is_some()
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ClientSessionStore for ReloadingTls {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.current().sessions.set_kx_hint(server_name, group)
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        self.current().sessions.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.current()
+            .sessions
+            .set_tls12_session(server_name, value)
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.refresh().sessions.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.current().sessions.remove_tls12_session(server_name)
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.current()
+            .sessions
+            .insert_tls13_ticket(server_name, value)
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.refresh().sessions.take_tls13_ticket(server_name)
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::CertificateRevocationListDer;
+
+#[cfg(not(feature = "native-tls"))]
+use super::roots::is_pem;
+
+/// A certificate revocation list published by a CA.
+#[derive(Clone, Debug)]
+pub enum CrlSource {
+    /// One or more PEM encoded CRLs.
+    Pem(Vec<u8>),
+    /// A single DER encoded CRL.
+    Der(Vec<u8>),
+    /// A PEM or DER CRL file, detected from its content and read when the client is built.
+    File(PathBuf),
+}
+
+/// Rejects server certificates revoked by one of the CRLs.
+///
+/// The defaults check the whole chain and reject a certificate whose issuer
+/// published none of the given CRLs.
+#[derive(Clone, Debug, Default)]
+pub struct RevocationCheck {
+    pub crls: Vec<CrlSource>,
+    /// Check only the leaf certificate, not the intermediates.
+    pub end_entity_only: bool,
+    /// Accept certificates whose revocation status no CRL covers.
+    pub allow_unknown_status: bool,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl CrlSource {
+    pub(crate) fn crls(&self) -> anyhow::Result<Vec<CertificateRevocationListDer<'static>>> {
+        match self {
+            CrlSource::Pem(bytes) => pem_crls(bytes),
+            CrlSource::Der(bytes) => Ok(vec![CertificateRevocationListDer::from(bytes.clone())]),
+            CrlSource::File(path) => {
+                let bytes = std::fs::read(path)?;
+                if is_pem(&bytes) {
+                    pem_crls(&bytes).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+                } else {
+                    Ok(vec![CertificateRevocationListDer::from(bytes)])
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+fn pem_crls(bytes: &[u8]) -> anyhow::Result<Vec<CertificateRevocationListDer<'static>>> {
+    let crls = rustls_pemfile::crls(&mut &bytes[..]).collect::<Result<Vec<_>, _>>()?;
+    if crls.is_empty() {
+        anyhow::bail!("no CRL found in PEM input");
+    }
+    Ok(crls)
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use rustls::pki_types::CertificateDer;
+#[cfg(not(feature = "native-tls"))]
+use rustls::RootCertStore;
+
+/// A source of trusted CA certificates.
+#[derive(Clone, Debug)]
+pub enum CaSource {
+    /// PEM bundle, every certificate in it is trusted.
+    Pem(Vec<u8>),
+    /// A single DER encoded certificate.
+    Der(Vec<u8>),
+    /// A PEM bundle or DER certificate file, detected from its content.
+    File(PathBuf),
+    /// Every PEM or DER certificate file directly inside the directory.
+    Dir(PathBuf),
+}
+
+/// How the certificates of the platform trust store are used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemRoots {
+    /// Trusted alongside the bundled web roots.
+    WithWebRoots,
+    /// Trusted in place of the bundled web roots.
+    Only,
+}
+
+impl CaSource {
+    /// Every certificate of the source, a directory may also yield files that are not certificates.
+    pub(crate) fn certificates(&self) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+        match self {
+            CaSource::Pem(bytes) => pem_certs(bytes),
+            CaSource::Der(bytes) => Ok(vec![CertificateDer::from(bytes.clone())]),
+            CaSource::File(path) => file_certs(path),
+            CaSource::Dir(path) => dir_certs(path),
+        }
+    }
+
+    #[cfg(not(feature = "native-tls"))]
+    pub(crate) fn add_to(&self, roots: &mut RootCertStore) -> anyhow::Result<()> {
+        let certs = self.certificates()?;
+        if let CaSource::Dir(path) = self {
+            // a certificate directory may hold unrelated files, only fail when nothing is usable
+            let (added, _ignored) = roots.add_parsable_certificates(certs);
+            if added == 0 {
+                anyhow::bail!("no certificate found in {}", path.display());
+            }
+            return Ok(());
+        }
+        for cert in certs {
+            roots.add(cert)?;
+        }
+        Ok(())
+    }
+}
+
+/// Certificates of the platform trust store, on linux the system CA bundle and
+/// directory, which `SSL_CERT_FILE` and `SSL_CERT_DIR` override.
+#[cfg(not(feature = "native-tls"))]
+pub(crate) fn system_certificates() -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let result = rustls_native_certs::load_native_certs();
+    if result.certs.is_empty() {
+        match result.errors.first() {
+            Some(err) => anyhow::bail!("cannot load the system trust store: {}", err),
+            None => anyhow::bail!("the system trust store is empty"),
+        }
+    }
+    Ok(result.certs)
+}
+
+pub(super) fn is_pem(bytes: &[u8]) -> bool {
+    bytes.windows(11).any(|window| window == b"-----BEGIN ")
+}
+
+fn pem_certs(bytes: &[u8]) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut &bytes[..]).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate found in PEM bundle");
+    }
+    Ok(certs)
+}
+
+fn file_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let bytes = std::fs::read(path)?;
+    if is_pem(&bytes) {
+        pem_certs(&bytes).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    } else {
+        Ok(vec![CertificateDer::from(bytes)])
+    }
+}
+
+fn dir_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let mut certs = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let file_path = entry?.path();
+        if !file_path.is_file() {
+            continue;
+        }
+        let bytes = std::fs::read(&file_path)?;
+        if is_pem(&bytes) {
+            certs.extend(rustls_pemfile::certs(&mut bytes.as_slice()).filter_map(Result::ok));
+        } else {
+            certs.push(CertificateDer::from(bytes));
+        }
+    }
+    Ok(certs)
+}
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use reqwest::ClientBuilder;
+use rustls::client::danger::ServerCertVerifier;
+use rustls::client::{Resumption, WebPkiServerVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::sign::CertifiedKey;
+use rustls::{ClientConfig, RootCertStore};
+use url::Url;
+
+use super::inspect::InspectTimeouts;
+use super::pins::PinningVerifier;
+use super::reload::ReloadingTls;
+use super::verifier::{HookVerifier, NoVerification, ScopedVerifier};
+use super::{protocol, roots};
+use super::{CaSource, CertificateInfo, CrlSource, SystemRoots, TlsConfig, TlsReport, TlsVersion};
+
+/// rustls client config shared by every connection of a reqwest client.
+pub(crate) type TlsConnector = Arc<ClientConfig>;
+
+pub(crate) fn use_connector(builder: ClientBuilder, connector: &TlsConnector) -> ClientBuilder {
+    builder.use_preconfigured_tls((**connector).clone())
+}
+
+#[cfg(feature = "fips")]
+fn crypto_provider() -> CryptoProvider {
+    rustls::crypto::default_fips_provider()
+}
+
+#[cfg(all(feature = "aws-lc-rs", not(feature = "fips")))]
+fn crypto_provider() -> CryptoProvider {
+    rustls::crypto::aws_lc_rs::default_provider()
+}
+
+#[cfg(all(feature = "ring", not(feature = "aws-lc-rs")))]
+fn crypto_provider() -> CryptoProvider {
+    rustls::crypto::ring::default_provider()
+}
+
+impl TlsConfig {
+    pub(crate) fn connector(&self) -> anyhow::Result<TlsConnector> {
+        Ok(Arc::new(self.client_config()?))
+    }
+
+    fn client_config(&self) -> anyhow::Result<ClientConfig> {
+        let mut provider = crypto_provider();
+        if let Some(cipher_suites) = &self.cipher_suites {
+            protocol::restrict_cipher_suites(&mut provider, cipher_suites)?;
+        }
+        let provider = Arc::new(provider);
+        let builder =
+            ClientConfig::builder_with_provider(provider.clone()).with_protocol_versions(
+                &protocol::protocol_versions(self.min_version, self.max_version)?,
+            )?;
+
+        let builder = builder.dangerous();
+
+        let mut config = match &self.material_source {
+            Some(source) => {
+                let reloading =
+                    Arc::new(ReloadingTls::new(self.clone(), provider, source.clone())?);
+                let mut config = builder
+                    .with_custom_certificate_verifier(reloading.clone())
+                    .with_client_cert_resolver(reloading.clone());
+                config.resumption = Resumption::store(reloading);
+                config
+            }
+            None => {
+                let builder =
+                    builder.with_custom_certificate_verifier(self.server_verifier(&provider)?);
+                match &self.client_identity {
+                    Some(identity) => {
+                        let (cert_chain, private_key) = identity.load()?;
+                        builder.with_client_auth_cert(cert_chain, private_key)?
+                    }
+                    None => builder.with_no_client_auth(),
+                }
+            }
+        };
+        if let Some(alpn_protocols) = &self.alpn_protocols {
+            config.alpn_protocols = protocol::alpn_protocols(alpn_protocols)?;
+        }
+        if let Some(key_log) = &self.key_log {
+            config.key_log = key_log.key_log()?;
+        }
+        Ok(config)
+    }
+
+    pub(super) fn server_verifier(
+        &self,
+        provider: &Arc<CryptoProvider>,
+    ) -> anyhow::Result<Arc<dyn ServerCertVerifier>> {
+        let mut verifier: Arc<dyn ServerCertVerifier> = if let Some(true) = self.insecure {
+            Arc::new(NoVerification::new(provider.clone()))
+        } else {
+            let mut builder = WebPkiServerVerifier::builder_with_provider(
+                Arc::new(self.root_store()?),
+                provider.clone(),
+            );
+            if let Some(revocation) = &self.revocation {
+                let crls = revocation
+                    .crls
+                    .iter()
+                    .map(CrlSource::crls)
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                builder = builder.with_crls(crls.into_iter().flatten());
+                if revocation.end_entity_only {
+                    builder = builder.only_check_end_entity_revocation();
+                }
+                if revocation.allow_unknown_status {
+                    builder = builder.allow_unknown_revocation_status();
+                }
+            }
+            builder.build()?
+        };
+        if let Some(trusted_hosts) = &self.trusted_hosts {
+            let strict = WebPkiServerVerifier::builder_with_provider(
+                Arc::new(self.public_roots()?),
+                provider.clone(),
+            )
+            .build()?;
+            verifier = Arc::new(ScopedVerifier::new(trusted_hosts.clone(), verifier, strict));
+        }
+        if let Some(hook) = &self.verifier {
+            verifier = Arc::new(HookVerifier::new(verifier, hook.clone()));
+        }
+        if let Some(pins) = &self.pins {
+            verifier = Arc::new(PinningVerifier::new(verifier, pins.clone()));
+        }
+        Ok(verifier)
+    }
+
+    pub(super) fn certified_key(
+        &self,
+        provider: &CryptoProvider,
+    ) -> anyhow::Result<Option<CertifiedKey>> {
+        match &self.client_identity {
+            Some(identity) => {
+                let (cert_chain, private_key) = identity.load()?;
+                Ok(Some(CertifiedKey::from_der(
+                    cert_chain,
+                    private_key,
+                    provider,
+                )?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The bundled web roots and the platform trust store, as `system_roots` asks.
+    fn public_roots(&self) -> anyhow::Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        if self.system_roots != Some(SystemRoots::Only) {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+        if self.system_roots.is_some() {
+            // system bundles carry the odd certificate webpki cannot parse, those are skipped
+            roots.add_parsable_certificates(roots::system_certificates()?);
+        }
+        Ok(roots)
+    }
+
+    fn root_store(&self) -> anyhow::Result<RootCertStore> {
+        let mut roots = if self.private_roots_only == Some(true) {
+            RootCertStore::empty()
+        } else {
+            self.public_roots()?
+        };
+        if let Some(private_chain_bytes) = &self.private_chain_bytes {
+            CaSource::Pem(private_chain_bytes.clone()).add_to(&mut roots)?;
+        }
+        for ca_source in self.ca_sources.iter().flatten() {
+            ca_source.add_to(&mut roots)?;
+        }
+        if roots.is_empty() {
+            anyhow::bail!("private_roots_only is set but no private CA was given");
+        }
+        Ok(roots)
+    }
+}
+
+pub(crate) async fn inspect(
+    connector: TlsConnector,
+    url: &Url,
+    timeouts: InspectTimeouts,
+) -> anyhow::Result<TlsReport> {
+    let (host, port) = super::inspect::target(url)?;
+    let server_name = ServerName::try_from(host.to_string())?;
+
+    let tcp_stream = timeouts
+        .connect(url, tokio::net::TcpStream::connect((host, port)))
+        .await??;
+    let tls_stream = timeouts
+        .handshake(
+            url,
+            tokio_rustls::TlsConnector::from(connector).connect(server_name, tcp_stream),
+        )
+        .await??;
+    let (_, connection) = tls_stream.get_ref();
+
+    let mut certificates = connection
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert: &CertificateDer<'_>| CertificateInfo::from_der(cert))
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter();
+    let peer_certificate = certificates
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} sent no certificate", host))?;
+
+    Ok(TlsReport {
+        protocol_version: connection
+            .protocol_version()
+            .and_then(|version| match version {
+                rustls::ProtocolVersion::TLSv1_2 => Some(TlsVersion::Tls12),
+                rustls::ProtocolVersion::TLSv1_3 => Some(TlsVersion::Tls13),
+                _ => None,
+            }),
+        cipher_suite: connection
+            .negotiated_cipher_suite()
+            .and_then(|suite| suite.suite().as_str())
+            .map(String::from),
+        alpn_protocol: connection
+            .alpn_protocol()
+            .map(|protocol| String::from_utf8_lossy(protocol).into_owned()),
+        peer_certificate,
+        intermediates: certificates.collect(),
+    })
+}
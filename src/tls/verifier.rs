@@ -0,0 +1,269 @@
+use std::fmt;
+#[cfg(not(feature = "native-tls"))]
+use std::sync::Arc;
+
+#[cfg(not(feature = "native-tls"))]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(not(feature = "native-tls"))]
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+#[cfg(not(feature = "native-tls"))]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::CertificateError;
+#[cfg(not(feature = "native-tls"))]
+use rustls::{DigitallySignedStruct, OtherError, SignatureScheme};
+
+#[cfg(not(feature = "native-tls"))]
+use super::HostPattern;
+
+/// Accepts any server certificate, only handshake signatures are still checked.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+pub(crate) struct NoVerification {
+    provider: Arc<CryptoProvider>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl NoVerification {
+    pub(crate) fn new(provider: Arc<CryptoProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Decides whether a server certificate is trusted, in place of the built-in validation.
+///
+/// The outcome of the built-in validation is still passed in through
+/// [`ServerCertificate::default_result`], so a verifier can relax it for a known
+/// case and keep it for everything else.
+pub trait CertificateVerifier: fmt::Debug + Send + Sync {
+    fn verify(&self, server: &ServerCertificate<'_>) -> CertificateVerdict;
+}
+
+/// What the server presented during the handshake.
+#[derive(Debug)]
+pub struct ServerCertificate<'a> {
+    pub end_entity: &'a [u8],
+    pub intermediates: Vec<&'a [u8]>,
+    pub server_name: &'a str,
+    pub ocsp_response: &'a [u8],
+    /// `Err` holds why the built-in validation against the configured roots failed.
+    pub default_result: Result<(), CertificateError>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertificateVerdict {
+    Accept,
+    Reject(String),
+}
+
+/// A [`CertificateVerifier`] refused the server certificate of `host`.
+#[derive(Clone, Debug)]
+pub struct CertificateRejectedError {
+    pub host: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CertificateRejectedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "server certificate of {} rejected: {}",
+            self.host, self.reason
+        )
+    }
+}
+
+impl std::error::Error for CertificateRejectedError {}
+
+/// Runs the built-in verifier and lets the [`CertificateVerifier`] have the last word.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+pub(crate) struct HookVerifier {
+    default: Arc<dyn ServerCertVerifier>,
+    hook: Arc<dyn CertificateVerifier>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl HookVerifier {
+    pub(crate) fn new(
+        default: Arc<dyn ServerCertVerifier>,
+        hook: Arc<dyn CertificateVerifier>,
+    ) -> Self {
+        Self { default, hook }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ServerCertVerifier for HookVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let default_result = match self.default.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Ok(_) => Ok(()),
+            Err(rustls::Error::InvalidCertificate(err)) => Err(err),
+            Err(err) => return Err(err),
+        };
+        let host = server_name.to_str();
+        let server = ServerCertificate {
+            end_entity,
+            intermediates: intermediates.iter().map(|cert| cert.as_ref()).collect(),
+            server_name: &host,
+            ocsp_response,
+            default_result,
+        };
+        match self.hook.verify(&server) {
+            CertificateVerdict::Accept => Ok(ServerCertVerified::assertion()),
+            CertificateVerdict::Reject(reason) => Err(rustls::Error::InvalidCertificate(
+                CertificateError::Other(OtherError(Arc::new(CertificateRejectedError {
+                    host: host.into_owned(),
+                    reason,
+                }))),
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.default.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.default.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.default.supported_verify_schemes()
+    }
+}
+
+/// Uses the relaxed verifier for hosts in scope and the strict one for everything else.
+#[cfg(not(feature = "native-tls"))]
+#[derive(Debug)]
+pub(crate) struct ScopedVerifier {
+    scope: Vec<HostPattern>,
+    relaxed: Arc<dyn ServerCertVerifier>,
+    strict: Arc<dyn ServerCertVerifier>,
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ScopedVerifier {
+    pub(crate) fn new(
+        scope: Vec<HostPattern>,
+        relaxed: Arc<dyn ServerCertVerifier>,
+        strict: Arc<dyn ServerCertVerifier>,
+    ) -> Self {
+        Self {
+            scope,
+            relaxed,
+            strict,
+        }
+    }
+}
+
+#[cfg(not(feature = "native-tls"))]
+impl ServerCertVerifier for ScopedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verifier = if self.scope.iter().any(|host| host.matches(server_name)) {
+            &self.relaxed
+        } else {
+            &self.strict
+        };
+        verifier.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.strict.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.strict.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.strict.supported_verify_schemes()
+    }
+}